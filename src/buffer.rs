@@ -0,0 +1,448 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Text of the line being edited with its cursor and undo history
+#[derive(Debug, Default)]
+pub struct Buffer {
+    pub text: String,
+    /// Byte offset of the cursor in `text`, always on a grapheme boundary
+    pub cursor: usize,
+    undo: Vec<(String, usize)>,
+}
+
+fn closing_pair(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '\'' | '"' | '`' => Some(c),
+        _ => None,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Remembers the current state so the next change can be undone
+    pub fn checkpoint(&mut self) {
+        if self.undo.last().map(|(text, _)| text) != Some(&self.text) {
+            self.undo.push((self.text.clone(), self.cursor));
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some((text, cursor)) = self.undo.pop() {
+            self.text = text;
+            self.cursor = cursor;
+        }
+    }
+
+    /// Replaces the whole line and moves the cursor to its end
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+    }
+
+    pub fn insert(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    /// Replaces `start..cursor` with `s`, leaving the cursor after it
+    pub fn replace_before_cursor(&mut self, start: usize, s: &str) {
+        self.text.replace_range(start..self.cursor, s);
+        self.cursor = start + s.len();
+    }
+
+    /// Removes `start..end` and returns it, keeping the cursor on the same text
+    pub fn remove(&mut self, start: usize, end: usize) -> String {
+        let removed = self.text[start..end].to_string();
+        self.text.replace_range(start..end, "");
+        if self.cursor >= end {
+            self.cursor -= end - start;
+        } else if self.cursor > start {
+            self.cursor = start;
+        }
+        removed
+    }
+
+    /// Start of the grapheme before `position`
+    pub fn previous_boundary(&self, position: usize) -> usize {
+        self.text[..position]
+            .grapheme_indices(true)
+            .next_back()
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    /// End of the grapheme at `position`
+    pub fn next_boundary(&self, position: usize) -> usize {
+        self.text[position..]
+            .graphemes(true)
+            .next()
+            .map(|g| position + g.len())
+            .unwrap_or(position)
+    }
+
+    pub fn delete_backward(&mut self) {
+        if self.cursor > 0 {
+            self.remove(self.previous_boundary(self.cursor), self.cursor);
+        }
+    }
+
+    /// Deletes backward, taking an empty pair of brackets or quotes around the cursor with it
+    pub fn delete_backward_paired(&mut self) {
+        let previous = self.text[..self.cursor].chars().next_back();
+        let next = self.text[self.cursor..].chars().next();
+        match (previous.and_then(closing_pair), next) {
+            (Some(close), Some(next)) if close == next => {
+                self.remove(self.cursor - 1, self.cursor + 1);
+            }
+            _ => self.delete_backward(),
+        }
+    }
+
+    /// Inserts `c` with the closing half when it opens a pair, or types over the closing half
+    /// when it is already under the cursor
+    pub fn insert_paired(&mut self, c: char) {
+        let previous = self.text[..self.cursor].chars().next_back();
+        let next = self.text[self.cursor..].chars().next();
+        if next == Some(c) && matches!(c, ')' | ']' | '}' | '\'' | '"' | '`') {
+            self.move_right();
+            return;
+        }
+
+        // Only pair before the end of a word, so quotes can still start a string in front of
+        // one, and not after a word character, like the apostrophe in it's
+        let free = next.is_none_or(|n| n.is_whitespace() || ")]},;".contains(n));
+        let after_word = previous.is_some_and(|p| p.is_alphanumeric() || p == '\\');
+        match closing_pair(c) {
+            Some(close) if free && !(close == c && after_word) => {
+                self.insert(&format!("{c}{close}"));
+                self.move_left();
+            }
+            _ => self.insert(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+
+    pub fn delete_forward(&mut self) {
+        self.remove(self.cursor, self.next_boundary(self.cursor));
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.previous_boundary(self.cursor);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_boundary(self.cursor);
+    }
+
+    pub fn move_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Start of the word before the cursor, where `is_word` decides from the first character
+    /// of each grapheme what a word is made of
+    pub fn word_start(&self, is_word: impl Fn(char) -> bool) -> usize {
+        let is_word = |g: &str| g.chars().next().is_some_and(&is_word);
+        let before: Vec<(usize, &str)> = self.text[..self.cursor].grapheme_indices(true).collect();
+        let mut i = before.len();
+        while i > 0 && !is_word(before[i - 1].1) {
+            i -= 1;
+        }
+        while i > 0 && is_word(before[i - 1].1) {
+            i -= 1;
+        }
+        before
+            .get(i)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.cursor)
+    }
+
+    /// End of the word after the cursor
+    pub fn word_end(&self, is_word: impl Fn(char) -> bool) -> usize {
+        let is_word = |g: &str| g.chars().next().is_some_and(&is_word);
+        let mut graphemes = self.text[self.cursor..].grapheme_indices(true).peekable();
+        while graphemes.next_if(|(_, g)| !is_word(g)).is_some() {}
+        while graphemes.next_if(|(_, g)| is_word(g)).is_some() {}
+        graphemes
+            .peek()
+            .map(|(i, _)| self.cursor + i)
+            .unwrap_or(self.text.len())
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.word_start(is_word_char);
+    }
+
+    pub fn move_word_right(&mut self) {
+        self.cursor = self.word_end(is_word_char);
+    }
+
+    pub fn kill_to_end(&mut self) -> String {
+        self.remove(self.cursor, self.text.len())
+    }
+
+    pub fn kill_to_start(&mut self) -> String {
+        self.remove(0, self.cursor)
+    }
+
+    /// Kills back to the previous whitespace, like readline's `unix-word-rubout`
+    pub fn kill_big_word_backward(&mut self) -> String {
+        self.remove(self.word_start(|c| !c.is_whitespace()), self.cursor)
+    }
+
+    pub fn kill_word_backward(&mut self) -> String {
+        self.remove(self.word_start(is_word_char), self.cursor)
+    }
+
+    pub fn kill_word_forward(&mut self) -> String {
+        self.remove(self.cursor, self.word_end(is_word_char))
+    }
+
+    /// Swaps the graphemes around the cursor, or the last two at the end of the line
+    pub fn transpose(&mut self) {
+        if self.cursor == 0 || self.text.graphemes(true).count() < 2 {
+            return;
+        }
+        if self.cursor == self.text.len() {
+            self.move_left();
+        }
+        let start = self.previous_boundary(self.cursor);
+        let end = self.next_boundary(self.cursor);
+        let first = self.text[start..self.cursor].to_string();
+        let second = self.text[self.cursor..end].to_string();
+        self.text.replace_range(start..end, &(second + &first));
+        self.cursor = end;
+    }
+
+    /// Applies `change` to the next word and moves past it
+    pub fn change_word(&mut self, change: impl Fn(&str) -> String) {
+        let end = self.word_end(is_word_char);
+        let changed = change(&self.text[self.cursor..end]);
+        self.text.replace_range(self.cursor..end, &changed);
+        self.cursor += changed.len();
+    }
+}
+
+/// Killed text available for yanking, most recent last
+#[derive(Debug, Default)]
+pub struct KillRing {
+    entries: Vec<String>,
+    /// Entry the last yank inserted, counted back from the most recent
+    offset: usize,
+}
+
+const KILL_RING_SIZE: usize = 60;
+
+impl KillRing {
+    /// Adds killed text, merging it into the last entry when kills happen back to back
+    pub fn kill(&mut self, text: String, append: bool, backward: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.entries.last_mut() {
+            Some(last) if append && backward => last.insert_str(0, &text),
+            Some(last) if append => last.push_str(&text),
+            _ => {
+                self.entries.push(text);
+                if self.entries.len() > KILL_RING_SIZE {
+                    self.entries.remove(0);
+                }
+            }
+        }
+        self.offset = 0;
+    }
+
+    pub fn yank(&mut self) -> Option<&str> {
+        self.offset = 0;
+        self.entries.last().map(|s| s.as_str())
+    }
+
+    /// Steps back to the entry before the one last yanked
+    pub fn rotate(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.offset = (self.offset + 1) % self.entries.len();
+        self.entries
+            .get(self.entries.len() - 1 - self.offset)
+            .map(|s| s.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A buffer holding `text` with the cursor where the `|` is
+    fn buffer(text: &str) -> Buffer {
+        let cursor = text.find('|').unwrap();
+        Buffer {
+            text: text.replacen('|', "", 1),
+            cursor,
+            undo: Vec::new(),
+        }
+    }
+
+    /// The text with a `|` at the cursor
+    fn shown(buffer: &Buffer) -> String {
+        let mut text = buffer.text.clone();
+        text.insert(buffer.cursor, '|');
+        text
+    }
+
+    #[test]
+    fn moves_over_whole_graphemes() {
+        let mut b = buffer("cafe\u{301}|");
+        b.move_left();
+        assert_eq!(shown(&b), "caf|e\u{301}");
+        b.move_left();
+        assert_eq!(shown(&b), "ca|fe\u{301}");
+        b.move_right();
+        b.move_right();
+        assert_eq!(shown(&b), "cafe\u{301}|");
+        // Nothing past either end
+        b.move_right();
+        assert_eq!(shown(&b), "cafe\u{301}|");
+        let mut b = buffer("|日本");
+        b.move_left();
+        assert_eq!(shown(&b), "|日本");
+        b.move_right();
+        assert_eq!(shown(&b), "日|本");
+    }
+
+    #[test]
+    fn deletes_whole_graphemes() {
+        let mut b = buffer("ae\u{301}|b");
+        b.delete_backward();
+        assert_eq!(shown(&b), "a|b");
+        let mut b = buffer("a|👍🏽b");
+        b.delete_forward();
+        assert_eq!(shown(&b), "a|b");
+        let mut b = buffer("|");
+        b.delete_backward();
+        b.delete_forward();
+        assert_eq!(shown(&b), "|");
+    }
+
+    #[test]
+    fn transposes_graphemes() {
+        let mut b = buffer("aé|");
+        b.transpose();
+        assert_eq!(shown(&b), "éa|");
+        let mut b = buffer("xe\u{301}|y");
+        b.transpose();
+        assert_eq!(shown(&b), "xye\u{301}|");
+    }
+
+    #[test]
+    fn moves_by_words_with_non_ascii_letters() {
+        let mut b = buffer("select nai\u{308}ve_col|");
+        b.move_word_left();
+        assert_eq!(shown(&b), "select |nai\u{308}ve_col");
+        b.move_word_left();
+        assert_eq!(shown(&b), "|select nai\u{308}ve_col");
+        let mut b = buffer("|select  日本 x");
+        b.move_word_right();
+        assert_eq!(shown(&b), "select|  日本 x");
+        b.move_word_right();
+        assert_eq!(shown(&b), "select  日本| x");
+    }
+
+    #[test]
+    fn kills_words_and_line_parts() {
+        let mut b = buffer("select foo.bär|");
+        assert_eq!(b.kill_word_backward(), "bär");
+        assert_eq!(shown(&b), "select foo.|");
+        let mut b = buffer("select foo.bär|");
+        assert_eq!(b.kill_big_word_backward(), "foo.bär");
+        assert_eq!(shown(&b), "select |");
+        let mut b = buffer("|  héllo world");
+        assert_eq!(b.kill_word_forward(), "  héllo");
+        assert_eq!(shown(&b), "| world");
+        let mut b = buffer("ab|ćd");
+        assert_eq!(b.kill_to_end(), "ćd");
+        assert_eq!(shown(&b), "ab|");
+        let mut b = buffer("ab|ćd");
+        assert_eq!(b.kill_to_start(), "ab");
+        assert_eq!(shown(&b), "|ćd");
+    }
+
+    #[test]
+    fn changes_the_case_of_the_next_word() {
+        let mut b = buffer("|straße x");
+        b.change_word(|word| word.to_uppercase());
+        assert_eq!(shown(&b), "STRASSE| x");
+    }
+
+    #[test]
+    fn undo_restores_text_and_cursor() {
+        let mut b = buffer("ab|");
+        b.checkpoint();
+        b.insert("é");
+        b.checkpoint();
+        b.move_start();
+        b.kill_to_end();
+        b.undo();
+        assert_eq!(shown(&b), "abé|");
+        b.undo();
+        assert_eq!(shown(&b), "ab|");
+        b.undo();
+        assert_eq!(shown(&b), "ab|");
+    }
+
+    #[test]
+    fn kill_ring_yanks_the_latest_kill_and_rotates() {
+        let mut ring = KillRing::default();
+        assert_eq!(ring.yank(), None);
+        assert_eq!(ring.rotate(), None);
+        ring.kill("première".to_string(), false, false);
+        ring.kill("zweite".to_string(), false, false);
+        ring.kill(String::new(), false, false);
+        assert_eq!(ring.yank(), Some("zweite"));
+        assert_eq!(ring.rotate(), Some("première"));
+        assert_eq!(ring.rotate(), Some("zweite"));
+        // Yanking again starts over from the latest
+        ring.rotate();
+        assert_eq!(ring.yank(), Some("zweite"));
+    }
+
+    #[test]
+    fn kill_ring_merges_consecutive_kills() {
+        let mut ring = KillRing::default();
+        ring.kill("foo ".to_string(), false, false);
+        ring.kill("bär".to_string(), true, false);
+        assert_eq!(ring.yank(), Some("foo bär"));
+        ring.kill("bär".to_string(), false, false);
+        ring.kill("foo ".to_string(), true, true);
+        assert_eq!(ring.yank(), Some("foo bär"));
+        assert_eq!(ring.rotate(), Some("foo bär"));
+    }
+
+    #[test]
+    fn kill_ring_forgets_the_oldest_kills() {
+        let mut ring = KillRing::default();
+        for i in 0..=KILL_RING_SIZE {
+            ring.kill(i.to_string(), false, false);
+        }
+        ring.yank();
+        for _ in 0..KILL_RING_SIZE - 1 {
+            ring.rotate();
+        }
+        assert_eq!(ring.rotate(), Some(KILL_RING_SIZE.to_string().as_str()));
+    }
+}
@@ -0,0 +1,767 @@
+use futures::future::BoxFuture;
+use sqlx::{Connection, MySqlConnection, Row};
+use std::io::Write;
+use std::path::Path;
+
+use crate::connector::server_status;
+use crate::describe::{self, Listing};
+use crate::editor::EditingMode;
+use crate::formatter;
+use crate::lexer::strip_backslash_command;
+use crate::session::Session;
+use crate::signal::ignore_interrupts;
+use crate::viewer::Viewer;
+
+#[derive(Debug, PartialEq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+type Handler = for<'a> fn(&'a mut Session, String) -> BoxFuture<'a, Flow>;
+
+/// A client-side command, run instead of being sent to the server
+pub struct MetaCommand {
+    /// Backslash form like `\q`, or a plain word for commands that have none
+    pub name: &'static str,
+    /// Words that also run the command at the start of a line, like `quit`
+    pub aliases: &'static [&'static str],
+    pub arguments: &'static str,
+    /// Whether the word forms take exactly one argument, leaving longer lines to the server
+    /// as SQL, like `use`
+    pub single_argument: bool,
+    pub description: &'static str,
+    pub run: Handler,
+}
+
+pub static COMMANDS: &[MetaCommand] = &[
+    MetaCommand {
+        name: "\\?",
+        aliases: &["help"],
+        arguments: "",
+        single_argument: false,
+        description: "Display this help",
+        run: help,
+    },
+    MetaCommand {
+        name: "\\c",
+        aliases: &[],
+        arguments: "",
+        single_argument: false,
+        description: "Cancel the current statement",
+        run: cancel,
+    },
+    MetaCommand {
+        name: "clear",
+        aliases: &[],
+        arguments: "",
+        single_argument: false,
+        description: "Clear the screen, also Ctrl-L",
+        run: clear_screen,
+    },
+    MetaCommand {
+        name: "\\r",
+        aliases: &["connect"],
+        arguments: "[database [host]]",
+        single_argument: false,
+        description: "Reconnect to the server",
+        run: connect,
+    },
+    MetaCommand {
+        name: "\\d",
+        aliases: &[],
+        arguments: "[table]",
+        single_argument: false,
+        description: "Describe a table or view, or list them all",
+        run: describe,
+    },
+    MetaCommand {
+        name: "\\df",
+        aliases: &[],
+        arguments: "[pattern]",
+        single_argument: false,
+        description: "List functions and procedures",
+        run: list_routines,
+    },
+    MetaCommand {
+        name: "\\di",
+        aliases: &[],
+        arguments: "[pattern]",
+        single_argument: false,
+        description: "List indexes",
+        run: list_indexes,
+    },
+    MetaCommand {
+        name: "\\dn",
+        aliases: &[],
+        arguments: "[pattern]",
+        single_argument: false,
+        description: "List databases, like \\l",
+        run: list_databases,
+    },
+    MetaCommand {
+        name: "\\dt",
+        aliases: &[],
+        arguments: "[pattern]",
+        single_argument: false,
+        description: "List tables",
+        run: list_tables,
+    },
+    MetaCommand {
+        name: "\\dv",
+        aliases: &[],
+        arguments: "[pattern]",
+        single_argument: false,
+        description: "List views",
+        run: list_views,
+    },
+    MetaCommand {
+        name: "\\e",
+        aliases: &[],
+        arguments: "",
+        single_argument: false,
+        description: "Edit the statement in $VISUAL or $EDITOR, also Ctrl-X Ctrl-E",
+        run: edit,
+    },
+    MetaCommand {
+        name: "\\G",
+        aliases: &[],
+        arguments: "",
+        single_argument: false,
+        description: "Send the statement and display the result vertically",
+        run: no_query,
+    },
+    MetaCommand {
+        name: "\\g",
+        aliases: &[],
+        arguments: "",
+        single_argument: false,
+        description: "Send the statement, like ;",
+        run: no_query,
+    },
+    MetaCommand {
+        name: "\\l",
+        aliases: &[],
+        arguments: "[pattern]",
+        single_argument: false,
+        description: "List databases",
+        run: list_databases,
+    },
+    MetaCommand {
+        name: "\\n",
+        aliases: &["nopager"],
+        arguments: "",
+        single_argument: false,
+        description: "Stop using the pager",
+        run: nopager,
+    },
+    MetaCommand {
+        name: "\\P",
+        aliases: &["pager"],
+        arguments: "[command]",
+        single_argument: false,
+        description: "Pipe long results to a pager, $PAGER or less by default",
+        run: set_pager,
+    },
+    MetaCommand {
+        name: "\\view",
+        aliases: &[],
+        arguments: "",
+        single_argument: false,
+        description: "Browse the last result in a scrollable grid",
+        run: view,
+    },
+    MetaCommand {
+        name: "\\q",
+        aliases: &["exit", "quit"],
+        arguments: "",
+        single_argument: false,
+        description: "Exit oxisql",
+        run: quit,
+    },
+    MetaCommand {
+        name: "\\#",
+        aliases: &["rehash"],
+        arguments: "",
+        single_argument: false,
+        description: "Reload the schema used for completion",
+        run: rehash,
+    },
+    MetaCommand {
+        name: "\\s",
+        aliases: &["status"],
+        arguments: "",
+        single_argument: false,
+        description: "Show the connection status",
+        run: status,
+    },
+    MetaCommand {
+        name: "\\set",
+        aliases: &["set editing-mode", "set auto-pairs"],
+        arguments: "editing-mode|auto-pairs value",
+        single_argument: false,
+        description: "Change an editor setting",
+        run: set,
+    },
+    MetaCommand {
+        name: "\\!",
+        aliases: &["system"],
+        arguments: "command",
+        single_argument: false,
+        description: "Run a shell command",
+        run: system,
+    },
+    MetaCommand {
+        name: "\\.",
+        aliases: &["source"],
+        arguments: "file",
+        single_argument: false,
+        description: "Run the statements in a file",
+        run: source,
+    },
+    MetaCommand {
+        name: "\\T",
+        aliases: &["tee"],
+        arguments: "file",
+        single_argument: false,
+        description: "Append everything to a file as well",
+        run: tee,
+    },
+    MetaCommand {
+        name: "\\t",
+        aliases: &["notee"],
+        arguments: "",
+        single_argument: false,
+        description: "Stop writing to the tee file",
+        run: notee,
+    },
+    MetaCommand {
+        name: "\\u",
+        aliases: &["use"],
+        arguments: "database",
+        single_argument: true,
+        description: "Use another database",
+        run: use_database,
+    },
+];
+
+/// Looks up the meta-command `input` starts with, returning it with its arguments. Returns
+/// `None` for SQL and an error for an unknown backslash command.
+pub fn parse(input: &str) -> Option<Result<(&'static MetaCommand, String), String>> {
+    let input = input.trim();
+    if let Some(rest) = input.strip_prefix('\\') {
+        let length = match rest.find(|c: char| !c.is_ascii_alphanumeric()) {
+            Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+            Some(length) => length,
+            None => rest.len(),
+        };
+        let name = &input[..length + 1];
+        let arguments = arguments(&input[length + 1..]);
+        return Some(
+            COMMANDS
+                .iter()
+                .find(|command| command.name == name)
+                .map(|command| (command, arguments))
+                .ok_or_else(|| format!("Unknown command {name}, see \\? for help")),
+        );
+    }
+
+    let words: Vec<String> = input
+        .trim_end_matches(';')
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect();
+    let (command, length) = COMMANDS.iter().find_map(|command| {
+        std::iter::once(&command.name)
+            .chain(command.aliases)
+            .filter(|alias| !alias.starts_with('\\'))
+            .map(|alias| alias.split_whitespace().collect::<Vec<&str>>())
+            .find(|alias| words.len() >= alias.len() && words[..alias.len()] == alias[..])
+            .map(|alias| (command, alias.len()))
+    })?;
+    if command.single_argument && words.len() != length + 1 {
+        return None;
+    }
+    let rest = input
+        .split_once(char::is_whitespace)
+        .map_or("", |(_, rest)| rest);
+    Some(Ok((command, arguments(rest))))
+}
+
+fn arguments(text: &str) -> String {
+    text.trim().trim_end_matches(';').trim_end().to_string()
+}
+
+/// How a statement typed at the prompt ends
+#[derive(Debug, PartialEq)]
+pub enum Terminator {
+    Semicolon,
+    /// `\G`, showing the result vertically
+    Vertical,
+    /// `\c`, dropping the statement
+    Cancel,
+}
+
+/// Splits a trailing `\g`, `\G` or `\c` off `input`, unless it is inside a string or comment
+pub fn split_terminator(input: &str) -> (&str, Terminator) {
+    let input = input.trim_end();
+    for (suffix, terminator) in [
+        ("\\G", Terminator::Vertical),
+        ("\\g", Terminator::Semicolon),
+        ("\\c", Terminator::Cancel),
+    ] {
+        if let Some(sql) = strip_backslash_command(input, suffix) {
+            return (sql, terminator);
+        }
+    }
+    (input, Terminator::Semicolon)
+}
+
+/// Whether `line` is ready to run, either as a meta-command or as terminated SQL
+pub fn is_complete(line: &str) -> bool {
+    let line = line.trim_end();
+    parse(line).is_some() || line.ends_with(';') || split_terminator(line).0.len() != line.len()
+}
+
+fn help(_session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        println!("List of all oxisql commands:");
+        for command in COMMANDS {
+            let aliases = command
+                .aliases
+                .iter()
+                .map(|alias| alias.split_whitespace().next().unwrap_or(alias))
+                .fold(Vec::new(), |mut aliases, alias| {
+                    if !aliases.contains(&alias) {
+                        aliases.push(alias);
+                    }
+                    aliases
+                })
+                .join(", ");
+            let usage = format!("{} {}", command.name, command.arguments);
+            println!(
+                "{:<36} {:<16} {}",
+                usage.trim_end(),
+                aliases,
+                command.description
+            );
+        }
+        println!("Statements end with ;, \\g or \\G and can be cancelled with \\c");
+        Flow::Continue
+    })
+}
+
+fn cancel(_session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move { Flow::Continue })
+}
+
+fn clear_screen(_session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let mut stdout = std::io::stdout();
+        stdout.write_all("\x1B[2J\x1B[1;1H".as_bytes()).unwrap();
+        Flow::Continue
+    })
+}
+
+fn connect(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let mut args = session.args.clone();
+        let mut words = arguments.split_whitespace();
+        if let Some(database) = words.next() {
+            args.database = Some(database.trim_matches('`').to_string());
+        } else {
+            args.database = session.current_database.clone();
+        }
+        if let Some(host) = words.next() {
+            args.host = host.to_string();
+        }
+
+        match MySqlConnection::connect(&args.url()).await {
+            Ok(connection) => {
+                *session.connection.lock().await = connection;
+                session.current_database = args.database.clone();
+                session.args = args;
+                session.refresh_schema();
+
+                let id = sqlx::query("SELECT CONNECTION_ID()")
+                    .fetch_one(&mut *session.connection.lock().await)
+                    .await
+                    .and_then(|row| row.try_get::<u64, _>(0));
+                println!("[+] Connected to MySQL server");
+                if let Ok(id) = id {
+                    println!("Connection id:    {}", id);
+                }
+                println!(
+                    "Current database: {}",
+                    session
+                        .current_database
+                        .as_deref()
+                        .unwrap_or("*** NONE ***")
+                );
+            }
+            Err(e) => eprintln!("[-] Could not connect to MySQL server: {}", e),
+        }
+        Flow::Continue
+    })
+}
+
+fn describe(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let database = session.current_database.clone();
+        if arguments.is_empty() || arguments.contains(['*', '?']) {
+            let listing =
+                describe::list_relations(&session.connection, &arguments, database.as_deref())
+                    .await;
+            print_listing(session, listing);
+            return Flow::Continue;
+        }
+
+        match describe::describe_table(&session.connection, &arguments, database.as_deref()).await {
+            Ok(Some(description)) => session.print_fitted(&description),
+            Ok(None) => eprintln!("[-] Did not find any relation named {}", arguments),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+        Flow::Continue
+    })
+}
+
+fn print_listing(session: &mut Session, listing: Result<Listing, sqlx::Error>) {
+    match listing {
+        Ok(listing) => session.print_fitted(&listing),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+fn list_databases(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let listing = describe::list_databases(&session.connection, &arguments).await;
+        print_listing(session, listing);
+        Flow::Continue
+    })
+}
+
+fn list_indexes(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let database = session.current_database.clone();
+        let listing =
+            describe::list_indexes(&session.connection, &arguments, database.as_deref()).await;
+        print_listing(session, listing);
+        Flow::Continue
+    })
+}
+
+fn list_routines(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let database = session.current_database.clone();
+        let listing =
+            describe::list_routines(&session.connection, &arguments, database.as_deref()).await;
+        print_listing(session, listing);
+        Flow::Continue
+    })
+}
+
+fn list_tables(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let database = session.current_database.clone();
+        let listing =
+            describe::list_tables(&session.connection, &arguments, database.as_deref()).await;
+        print_listing(session, listing);
+        Flow::Continue
+    })
+}
+
+fn list_views(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let database = session.current_database.clone();
+        let listing =
+            describe::list_views(&session.connection, &arguments, database.as_deref()).await;
+        print_listing(session, listing);
+        Flow::Continue
+    })
+}
+
+fn edit(_session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    // The line editor opens the editor itself when a line ends with \e, so this only runs for
+    // a \e read from a script
+    Box::pin(async move {
+        eprintln!("[-] \\e only works at the prompt");
+        Flow::Continue
+    })
+}
+
+fn no_query(_session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        eprintln!("[-] No query specified");
+        Flow::Continue
+    })
+}
+
+fn nopager(session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        session.pager = None;
+        println!("[+] PAGER set to stdout");
+        Flow::Continue
+    })
+}
+
+fn set_pager(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        session.set_pager(&arguments);
+        Flow::Continue
+    })
+}
+
+fn view(session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        match &session.last_result {
+            Some(table) if !table.values.is_empty() => {
+                if let Err(e) = Viewer::new(table).run() {
+                    eprintln!("[-] Could not show the result: {}", e);
+                }
+            }
+            Some(_) => eprintln!("[-] The last result is empty"),
+            None => eprintln!("[-] No result to view yet"),
+        }
+        Flow::Continue
+    })
+}
+
+fn quit(_session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move { Flow::Quit })
+}
+
+fn rehash(session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        session.refresh_schema();
+        println!("[+] Reloading the schema");
+        Flow::Continue
+    })
+}
+
+fn status(session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        match server_status(&session.connection).await {
+            Ok(mut entries) => {
+                let tee = match &session.tee {
+                    Some(tee) => tee.path().display().to_string(),
+                    None => "off".to_string(),
+                };
+                let auto_pairs = if session.editor.auto_pairs() {
+                    "on"
+                } else {
+                    "off"
+                };
+                // Results are always timed, and shown vertically only with \G
+                let format = match formatter::terminal_width() {
+                    Some(width) => format!("table, fitted to {} columns", width),
+                    None => "table".to_string(),
+                };
+                entries.insert(0, ("Client version", env!("CARGO_PKG_VERSION").to_string()));
+                entries.extend([
+                    (
+                        "Connection",
+                        format!("{}:{}", session.args.host, session.args.port),
+                    ),
+                    ("Editing mode", session.editor.editing_mode().to_string()),
+                    ("Auto-pairs", auto_pairs.to_string()),
+                    ("Output format", format),
+                    ("Timing", "on".to_string()),
+                    (
+                        "Pager",
+                        session
+                            .pager
+                            .clone()
+                            .unwrap_or_else(|| "stdout".to_string()),
+                    ),
+                    ("Tee", tee),
+                ]);
+                session.print(&formatter::status(&entries));
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+        Flow::Continue
+    })
+}
+
+fn set(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        match arguments.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["editing-mode", mode] => match mode.parse::<EditingMode>() {
+                Ok(editing_mode) => {
+                    session.editor.set_editing_mode(editing_mode);
+                    println!("[+] Editing mode set to {}", mode);
+                }
+                Err(e) => eprintln!("[-] {}", e),
+            },
+            ["auto-pairs", value @ ("on" | "off")] => {
+                session.editor.set_auto_pairs(*value == "on");
+                println!("[+] Auto-pairs turned {}", value);
+            }
+            _ => eprintln!("[-] Usage: \\set editing-mode emacs|vi or \\set auto-pairs on|off"),
+        }
+        Flow::Continue
+    })
+}
+
+fn system(_session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        if arguments.is_empty() {
+            eprintln!("[-] Usage: \\! command");
+            return Flow::Continue;
+        }
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&arguments)
+            .spawn()
+            .and_then(|mut child| {
+                // Ctrl-C interrupts the command alone, not the session waiting on it
+                let _interrupts = ignore_interrupts();
+                child.wait()
+            });
+        match status {
+            Ok(status) if !status.success() => eprintln!("[-] Command exited with {}", status),
+            Ok(_) => {}
+            Err(e) => eprintln!("[-] Could not run command: {}", e),
+        }
+        Flow::Continue
+    })
+}
+
+fn source(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        if arguments.is_empty() {
+            eprintln!("[-] Usage: \\. file");
+            return Flow::Continue;
+        }
+        match std::fs::read_to_string(&arguments) {
+            Ok(script) => {
+                session.run_statements(&script, false).await;
+            }
+            Err(e) => eprintln!("[-] Could not read {}: {}", arguments, e),
+        }
+        Flow::Continue
+    })
+}
+
+fn tee(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        if arguments.is_empty() {
+            eprintln!("[-] Usage: \\T file");
+            return Flow::Continue;
+        }
+        session.set_tee(Path::new(&arguments));
+        Flow::Continue
+    })
+}
+
+fn notee(session: &mut Session, _arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        if session.tee.take().is_some() {
+            println!("[+] Outfile disabled");
+        }
+        Flow::Continue
+    })
+}
+
+fn use_database(session: &mut Session, arguments: String) -> BoxFuture<'_, Flow> {
+    Box::pin(async move {
+        let database = arguments.trim_matches('`');
+        if database.is_empty() {
+            eprintln!("[-] Usage: \\u database");
+        } else {
+            let statement = format!("USE `{}`", database.replace('`', "``"));
+            session.execute(&statement, false).await;
+        }
+        Flow::Continue
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(input: &str) -> Option<Result<(&'static str, String), String>> {
+        parse(input).map(|parsed| parsed.map(|(command, arguments)| (command.name, arguments)))
+    }
+
+    #[test]
+    fn parse_reads_backslash_commands_and_arguments() {
+        assert_eq!(
+            name("\\dt users%"),
+            Some(Ok(("\\dt", "users%".to_string())))
+        );
+        assert_eq!(name("\\G"), Some(Ok(("\\G", String::new()))));
+        assert_eq!(name("\\u shop;"), Some(Ok(("\\u", "shop".to_string()))));
+        assert_eq!(name("\\!ls -l"), Some(Ok(("\\!", "ls -l".to_string()))));
+        assert_eq!(
+            name("\\x"),
+            Some(Err("Unknown command \\x, see \\? for help".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_matches_word_aliases_ignoring_case() {
+        assert_eq!(name("QUIT"), Some(Ok(("\\q", String::new()))));
+        assert_eq!(name("use shop;"), Some(Ok(("\\u", "shop".to_string()))));
+        assert_eq!(
+            name("set editing-mode vi;"),
+            Some(Ok(("\\set", "editing-mode vi".to_string())))
+        );
+    }
+
+    #[test]
+    fn parse_leaves_sql_to_the_server() {
+        assert_eq!(name("SET autocommit=0;"), None);
+        assert_eq!(name("use a b;"), None);
+        assert_eq!(name("select 'a\\G'"), None);
+        assert_eq!(name("statuses"), None);
+    }
+
+    #[test]
+    fn split_terminator_strips_trailing_terminators() {
+        assert_eq!(
+            split_terminator("select 1\\G "),
+            ("select 1", Terminator::Vertical)
+        );
+        assert_eq!(
+            split_terminator("select 1\\g"),
+            ("select 1", Terminator::Semicolon)
+        );
+        assert_eq!(
+            split_terminator("select 1\\c"),
+            ("select 1", Terminator::Cancel)
+        );
+        assert_eq!(
+            split_terminator("select 1;"),
+            ("select 1;", Terminator::Semicolon)
+        );
+    }
+
+    #[test]
+    fn split_terminator_ignores_terminators_in_strings_and_comments() {
+        assert_eq!(
+            split_terminator("select 'a\\G'"),
+            ("select 'a\\G'", Terminator::Semicolon)
+        );
+        assert_eq!(
+            split_terminator("select 'a\\G"),
+            ("select 'a\\G", Terminator::Semicolon)
+        );
+        assert_eq!(
+            split_terminator("select 1 -- \\c"),
+            ("select 1 -- \\c", Terminator::Semicolon)
+        );
+    }
+
+    #[test]
+    fn is_complete_needs_a_terminator_or_a_meta_command() {
+        assert!(is_complete("select 1;"));
+        assert!(is_complete("select 1\\G"));
+        assert!(is_complete("\\dt"));
+        assert!(is_complete("quit"));
+        assert!(is_complete("SET autocommit=0;"));
+        assert!(!is_complete("SET autocommit=0"));
+        assert!(!is_complete("select 'a\\G"));
+        assert!(!is_complete("use a b"));
+    }
+}
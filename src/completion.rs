@@ -0,0 +1,721 @@
+use crate::commands::{self, Terminator};
+use crate::editor::Helper;
+use crate::highlight::Highlighter;
+use crate::keywords::{function, FUNCTIONS, KEYWORDS};
+use crate::lexer::{find_imbalance, split_statements, tokenize, Token, TokenKind};
+use crate::schema::{Schema, SharedSchema};
+use crate::trie::fuzzy_score;
+
+/// Keywords that can start a statement
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "SELECT", "INSERT", "UPDATE", "DELETE", "REPLACE", "WITH", "USE", "SHOW", "DESCRIBE", "DESC",
+    "EXPLAIN", "CREATE", "ALTER", "DROP", "TRUNCATE", "SET", "BEGIN", "COMMIT", "ROLLBACK", "CALL",
+];
+
+/// Keywords that can follow a table reference or an expression
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "FROM",
+    "WHERE",
+    "JOIN",
+    "LEFT JOIN",
+    "RIGHT JOIN",
+    "INNER JOIN",
+    "CROSS JOIN",
+    "ON",
+    "USING",
+    "AS",
+    "AND",
+    "OR",
+    "NOT",
+    "IN",
+    "IS",
+    "NULL",
+    "LIKE",
+    "BETWEEN",
+    "GROUP BY",
+    "ORDER BY",
+    "HAVING",
+    "LIMIT",
+    "OFFSET",
+    "ASC",
+    "DESC",
+    "UNION",
+    "DISTINCT",
+    "VALUES",
+    "SET",
+    "INTO",
+];
+
+/// Words that can follow a table name and therefore are never its alias
+const NON_ALIAS_KEYWORDS: &[&str] = &[
+    "WHERE",
+    "ON",
+    "USING",
+    "JOIN",
+    "LEFT",
+    "RIGHT",
+    "INNER",
+    "CROSS",
+    "OUTER",
+    "NATURAL",
+    "STRAIGHT_JOIN",
+    "GROUP",
+    "ORDER",
+    "LIMIT",
+    "HAVING",
+    "SET",
+    "UNION",
+    "WINDOW",
+    "FOR",
+    "LOCK",
+    "VALUES",
+    "VALUE",
+    "SELECT",
+    "PARTITION",
+    "FORCE",
+    "USE",
+    "IGNORE",
+];
+
+#[derive(Debug, PartialEq)]
+enum Context {
+    StatementStart,
+    Keyword,
+    Table,
+    /// Tables of the named database, after `db.`
+    TableIn(String),
+    Database,
+    /// Columns, optionally restricted to a table name or alias qualifier
+    Column(Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateKind {
+    Keyword,
+    Function,
+    Table,
+    View,
+    Column,
+    Database,
+    Symbol,
+}
+
+impl CandidateKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CandidateKind::Keyword => "keyword",
+            CandidateKind::Function => "function",
+            CandidateKind::Table => "table",
+            CandidateKind::View => "view",
+            CandidateKind::Column => "column",
+            CandidateKind::Database => "database",
+            CandidateKind::Symbol => "symbol",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub text: String,
+    pub kind: CandidateKind,
+    /// Column type or function signature
+    pub detail: Option<String>,
+}
+
+impl Candidate {
+    fn new(text: &str, kind: CandidateKind) -> Self {
+        Self {
+            text: text.to_string(),
+            kind,
+            detail: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Completion {
+    /// Byte offset in the input where the completed word starts
+    pub start: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+impl Completion {
+    /// The longest prefix shared by all candidates, compared case-insensitively
+    pub fn common_prefix(&self) -> &str {
+        let Some((first, rest)) = self.candidates.split_first() else {
+            return "";
+        };
+        let mut end = first.text.len();
+        for candidate in rest {
+            let shared = first
+                .text
+                .char_indices()
+                .zip(candidate.text.chars())
+                .find(|((_, a), b)| !a.to_lowercase().eq(b.to_lowercase()))
+                .map(|((i, _), _)| i)
+                .unwrap_or(first.text.len().min(candidate.text.len()));
+            end = end.min(shared);
+        }
+        &first.text[..end]
+    }
+}
+
+/// A table referenced by the statement, with its alias if it has one
+#[derive(Debug, PartialEq)]
+struct TableReference {
+    name: String,
+    alias: Option<String>,
+}
+
+impl TableReference {
+    /// Whether the statement refers to this table as `qualifier`
+    fn is_named(&self, qualifier: &str) -> bool {
+        self.alias
+            .as_deref()
+            .unwrap_or(&self.name)
+            .eq_ignore_ascii_case(qualifier)
+    }
+}
+
+fn upper(input: &str, token: &Token) -> String {
+    token.text(input).to_uppercase()
+}
+
+fn identifier(input: &str, token: &Token) -> Option<String> {
+    match token.kind {
+        TokenKind::Word => Some(token.text(input).to_string()),
+        TokenKind::QuotedIdentifier => Some(token.text(input).trim_matches('`').to_string()),
+        _ => None,
+    }
+}
+
+pub fn complete(schema: &Schema, input: &str, cursor: usize) -> Completion {
+    let mut statement: Vec<Token> = Vec::new();
+    for token in tokenize(input) {
+        if token.kind == TokenKind::Punctuation && token.text(input) == ";" {
+            if token.end <= cursor {
+                statement.clear();
+                continue;
+            }
+            break;
+        }
+        statement.push(token);
+    }
+
+    let mut start = cursor;
+    if let Some(token) = statement
+        .iter()
+        .find(|t| t.start < cursor && cursor <= t.end)
+    {
+        match token.kind {
+            TokenKind::Word => start = token.start,
+            // Keep the opening backtick and complete the name inside it
+            TokenKind::QuotedIdentifier => start = token.start + 1,
+            TokenKind::String | TokenKind::Comment => {
+                return Completion {
+                    start,
+                    candidates: Vec::new(),
+                }
+            }
+            _ => {}
+        }
+    }
+    let prefix = &input[start..cursor];
+
+    let before: Vec<Token> = statement
+        .iter()
+        .filter(|t| t.end <= start && t.is_significant())
+        .copied()
+        .collect();
+    let significant: Vec<Token> = statement
+        .iter()
+        .filter(|t| t.is_significant())
+        .copied()
+        .collect();
+
+    let references = referenced_tables(input, &significant);
+    let context = match context(input, &before) {
+        // `db.` qualifies a table rather than a column, unless a table is also called that
+        Context::Column(Some(qualifier)) if !references.iter().any(|r| r.is_named(&qualifier)) => {
+            match schema.database(&qualifier) {
+                Some(database) => Context::TableIn(database.to_string()),
+                None => Context::Column(Some(qualifier)),
+            }
+        }
+        context => context,
+    };
+
+    let mut matches = Matches::new(prefix);
+    match context {
+        Context::StatementStart => {
+            matches.extend(STATEMENT_KEYWORDS.iter().copied(), CandidateKind::Keyword)
+        }
+        Context::Keyword => {
+            matches.extend(CLAUSE_KEYWORDS.iter().copied(), CandidateKind::Keyword);
+            matches.extend(KEYWORDS.iter().copied(), CandidateKind::Keyword);
+        }
+        Context::Table => matches.add_tables(schema),
+        Context::TableIn(database) if schema.database.as_deref() == Some(&database) => {
+            matches.add_tables(schema)
+        }
+        Context::TableIn(database) => matches.extend(
+            schema
+                .database_tables
+                .get(&database)
+                .into_iter()
+                .flatten()
+                .map(|t| t.as_str()),
+            CandidateKind::Table,
+        ),
+        Context::Database => matches.extend(
+            schema.databases.iter().map(|d| d.as_str()),
+            CandidateKind::Database,
+        ),
+        Context::Column(qualifier) => {
+            let tables: Vec<&str> = match &qualifier {
+                Some(qualifier) => references
+                    .iter()
+                    .find(|r| r.is_named(qualifier))
+                    .map(|r| vec![r.name.as_str()])
+                    .unwrap_or_else(|| vec![qualifier.as_str()]),
+                None => references.iter().map(|r| r.name.as_str()).collect(),
+            };
+
+            let columns = tables
+                .iter()
+                .filter_map(|name| schema.table(name))
+                .flat_map(|table| table.columns.iter());
+            for column in columns {
+                matches.add(
+                    &column.name,
+                    Candidate {
+                        text: column.name.clone(),
+                        kind: CandidateKind::Column,
+                        detail: Some(column.data_type.clone()),
+                    },
+                );
+            }
+            if qualifier.is_none() {
+                if matches.is_empty() {
+                    // Nothing to narrow down by yet, so fall back to every known symbol
+                    matches.extend(
+                        schema
+                            .symbols
+                            .fuzzy_search(prefix)
+                            .iter()
+                            .map(|s| s.as_str()),
+                        CandidateKind::Symbol,
+                    );
+                }
+                for function in FUNCTIONS {
+                    matches.add(
+                        function.name,
+                        Candidate {
+                            text: format!("{}(", function.name),
+                            kind: CandidateKind::Function,
+                            detail: Some(function.signature.to_string()),
+                        },
+                    );
+                }
+                matches.extend(KEYWORDS.iter().copied(), CandidateKind::Keyword);
+            }
+        }
+    }
+    let candidates = matches.into_candidates();
+
+    Completion { start, candidates }
+}
+
+/// Returns the signature of the innermost function whose arguments the cursor is in
+pub fn signature_hint(input: &str, cursor: usize) -> Option<&'static str> {
+    let mut calls: Vec<Option<&'static str>> = Vec::new();
+    let mut previous: Option<Token> = None;
+
+    for token in tokenize(&input[..cursor]) {
+        if !token.is_significant() {
+            continue;
+        }
+        match token.text(input) {
+            "(" => calls.push(
+                previous
+                    .filter(|t| t.kind == TokenKind::Word)
+                    .and_then(|t| function(t.text(input)))
+                    .map(|f| f.signature),
+            ),
+            ")" => {
+                calls.pop();
+            }
+            ";" => calls.clear(),
+            _ => {}
+        }
+        previous = Some(token);
+    }
+
+    calls.pop().flatten()
+}
+
+/// Line editor support for SQL statements, completing from the current schema
+pub struct SqlHelper {
+    pub schema: SharedSchema,
+    pub highlighter: Highlighter,
+}
+
+impl Helper for SqlHelper {
+    fn complete(&self, line: &str, cursor: usize) -> Completion {
+        complete(&self.schema.lock().unwrap(), line, cursor)
+    }
+
+    fn hint(&self, line: &str, cursor: usize) -> Option<String> {
+        signature_hint(line, cursor).map(String::from)
+    }
+
+    fn is_complete(&self, line: &str) -> bool {
+        commands::is_complete(line)
+    }
+
+    fn highlight(&self, line: &str, cursor: Option<usize>) -> String {
+        let schema = self.schema.lock().unwrap();
+        self.highlighter
+            .highlight(line, cursor, |name| schema.is_symbol(name))
+    }
+
+    fn check(&self, line: &str) -> Option<(usize, String)> {
+        if commands::parse(line).is_some() {
+            return None;
+        }
+        match commands::split_terminator(line) {
+            (_, Terminator::Cancel) => None,
+            (sql, _) => find_imbalance(sql),
+        }
+    }
+
+    fn statement_count(&self, line: &str) -> usize {
+        split_statements(line).len()
+    }
+}
+
+fn context(input: &str, before: &[Token]) -> Context {
+    let Some(last) = before.last() else {
+        return Context::StatementStart;
+    };
+    let last_text = last.text(input);
+
+    if last_text == "." && before.len() >= 2 {
+        return Context::Column(identifier(input, &before[before.len() - 2]));
+    }
+
+    for (i, token) in before.iter().enumerate().rev() {
+        if token.kind != TokenKind::Word {
+            continue;
+        }
+        let is_last = i == before.len() - 1;
+        match upper(input, token).as_str() {
+            "FROM" | "JOIN" | "UPDATE" => {
+                // A table name is expected right after the keyword or a comma in a table list
+                return if is_last || last_text == "," {
+                    Context::Table
+                } else {
+                    Context::Keyword
+                };
+            }
+            "INTO" => {
+                return if is_last {
+                    Context::Table
+                } else if before[i..].iter().any(|t| t.text(input) == "(") {
+                    Context::Column(None)
+                } else {
+                    Context::Keyword
+                };
+            }
+            "TABLE" | "DESC" | "DESCRIBE" if i == 0 => {
+                return if is_last {
+                    Context::Table
+                } else {
+                    Context::Keyword
+                };
+            }
+            "USE" if i == 0 => {
+                return if is_last {
+                    Context::Database
+                } else {
+                    Context::Keyword
+                };
+            }
+            "SELECT" | "WHERE" | "ON" | "USING" | "SET" | "BY" | "HAVING" | "AND" | "OR"
+            | "NOT" => return Context::Column(None),
+            _ => {}
+        }
+    }
+
+    Context::Keyword
+}
+
+fn referenced_tables(input: &str, tokens: &[Token]) -> Vec<TableReference> {
+    let mut references = Vec::new();
+    let mut in_table_list = false;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let text = upper(input, token);
+        let expects_table = match text.as_str() {
+            "FROM" | "UPDATE" => {
+                in_table_list = true;
+                true
+            }
+            "JOIN" | "INTO" => {
+                in_table_list = false;
+                true
+            }
+            "," => in_table_list,
+            _ => {
+                if NON_ALIAS_KEYWORDS.contains(&text.as_str()) {
+                    in_table_list = false;
+                }
+                false
+            }
+        };
+        if !expects_table {
+            continue;
+        }
+
+        let mut next = i + 1;
+        let Some(mut name) = tokens.get(next).and_then(|t| identifier(input, t)) else {
+            continue;
+        };
+        // Drop the database part of `db.table`, and skip a `db.` still waiting for its table
+        if tokens.get(next + 1).map(|t| t.text(input)) == Some(".") {
+            let Some(table) = tokens.get(next + 2).and_then(|t| identifier(input, t)) else {
+                continue;
+            };
+            name = table;
+            next += 2;
+        }
+
+        let mut alias_token = tokens.get(next + 1);
+        if alias_token.map(|t| upper(input, t)).as_deref() == Some("AS") {
+            alias_token = tokens.get(next + 2);
+        }
+        let alias = alias_token
+            .filter(|t| !NON_ALIAS_KEYWORDS.contains(&upper(input, t).as_str()))
+            .and_then(|t| identifier(input, t));
+
+        references.push(TableReference { name, alias });
+    }
+
+    references
+}
+
+/// Collects candidates matching a prefix case-insensitively, followed by fuzzy matches
+struct Matches {
+    prefix: String,
+    exact: Vec<Candidate>,
+    fuzzy: Vec<(i64, Candidate)>,
+}
+
+impl Matches {
+    fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_lowercase(),
+            exact: Vec::new(),
+            fuzzy: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.fuzzy.is_empty()
+    }
+
+    fn contains(&self, text: &str) -> bool {
+        self.exact.iter().any(|c| c.text == text) || self.fuzzy.iter().any(|(_, c)| c.text == text)
+    }
+
+    /// Adds `candidate` if `name` matches the prefix
+    fn add(&mut self, name: &str, candidate: Candidate) {
+        if self.contains(&candidate.text) {
+            return;
+        }
+        if name.to_lowercase().starts_with(&self.prefix) {
+            self.exact.push(candidate);
+        } else if let Some(score) = fuzzy_score(&self.prefix, name) {
+            self.fuzzy.push((score, candidate));
+        }
+    }
+
+    /// Adds the tables and views of the current database
+    fn add_tables(&mut self, schema: &Schema) {
+        for table in &schema.tables {
+            let kind = if table.is_view {
+                CandidateKind::View
+            } else {
+                CandidateKind::Table
+            };
+            self.add(&table.name, Candidate::new(&table.name, kind));
+        }
+    }
+
+    fn extend<'a>(&mut self, names: impl Iterator<Item = &'a str>, kind: CandidateKind) {
+        for name in names {
+            self.add(name, Candidate::new(name, kind));
+        }
+    }
+
+    fn into_candidates(mut self) -> Vec<Candidate> {
+        self.fuzzy
+            .sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.exact
+            .into_iter()
+            .chain(self.fuzzy.into_iter().map(|(_, candidate)| candidate))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{Column, Table};
+
+    fn schema() -> Schema {
+        let table = |name: &str, columns: &[&str]| Table {
+            name: name.to_string(),
+            is_view: false,
+            columns: columns
+                .iter()
+                .map(|column| Column {
+                    name: column.to_string(),
+                    data_type: "int".to_string(),
+                })
+                .collect(),
+            indexes: Vec::new(),
+        };
+        Schema {
+            database: Some("shop".to_string()),
+            databases: vec!["shop".to_string(), "logs".to_string()],
+            database_tables: [("logs".to_string(), vec!["events".to_string()])].into(),
+            tables: vec![
+                table("orders", &["id", "total"]),
+                table("users", &["id", "name"]),
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// Candidates for completing at the end of `input`
+    fn candidates(input: &str) -> Vec<String> {
+        complete(&schema(), input, input.len())
+            .candidates
+            .into_iter()
+            .map(|candidate| candidate.text)
+            .collect()
+    }
+
+    /// Context for completing a new word at the end of `input`
+    fn context_of(input: &str) -> Context {
+        let tokens: Vec<Token> = tokenize(input)
+            .into_iter()
+            .filter(|t| t.is_significant())
+            .collect();
+        context(input, &tokens)
+    }
+
+    fn references(input: &str) -> Vec<(String, Option<String>)> {
+        let tokens: Vec<Token> = tokenize(input)
+            .into_iter()
+            .filter(|t| t.is_significant())
+            .collect();
+        referenced_tables(input, &tokens)
+            .into_iter()
+            .map(|r| (r.name, r.alias))
+            .collect()
+    }
+
+    #[test]
+    fn context_follows_the_clause() {
+        assert_eq!(context_of(""), Context::StatementStart);
+        assert_eq!(context_of("select "), Context::Column(None));
+        assert_eq!(context_of("select * from "), Context::Table);
+        assert_eq!(context_of("select * from a, "), Context::Table);
+        assert_eq!(context_of("select * from a join "), Context::Table);
+        assert_eq!(context_of("select * from a "), Context::Keyword);
+        assert_eq!(context_of("select * from a where "), Context::Column(None));
+        assert_eq!(
+            context_of("select * from a where x = 1 and "),
+            Context::Column(None)
+        );
+        assert_eq!(context_of("insert into "), Context::Table);
+        assert_eq!(context_of("insert into a ("), Context::Column(None));
+        assert_eq!(context_of("use "), Context::Database);
+    }
+
+    #[test]
+    fn context_after_a_dot_is_qualified() {
+        assert_eq!(
+            context_of("select o."),
+            Context::Column(Some("o".to_string()))
+        );
+        assert_eq!(
+            context_of("select `o`."),
+            Context::Column(Some("o".to_string()))
+        );
+    }
+
+    #[test]
+    fn referenced_tables_reads_aliases() {
+        assert_eq!(
+            references("select * from orders o join shop.users as u on o.id = u.id where"),
+            [
+                ("orders".to_string(), Some("o".to_string())),
+                ("users".to_string(), Some("u".to_string())),
+            ]
+        );
+        assert_eq!(
+            references("select * from orders, users where"),
+            [("orders".to_string(), None), ("users".to_string(), None)]
+        );
+        assert_eq!(
+            references("update orders set"),
+            [("orders".to_string(), None)]
+        );
+        // A database still waiting for its table is not a table
+        assert!(references("select * from shop.").is_empty());
+    }
+
+    #[test]
+    fn complete_tables_after_from_and_join() {
+        assert_eq!(candidates("select * from "), ["orders", "users"]);
+        assert_eq!(candidates("select * from orders join us"), ["users"]);
+    }
+
+    #[test]
+    fn complete_columns_of_referenced_tables() {
+        let columns = candidates("select * from users where na");
+        assert_eq!(columns.first().map(String::as_str), Some("name"));
+        assert!(!columns.contains(&"total".to_string()));
+    }
+
+    #[test]
+    fn complete_columns_through_aliases() {
+        assert_eq!(
+            candidates("select * from orders o where o."),
+            ["id", "total"]
+        );
+        // Aliases defined after the cursor count too
+        let input = "select u. from orders o join users u";
+        let columns: Vec<String> = complete(&schema(), input, "select u.".len())
+            .candidates
+            .into_iter()
+            .map(|candidate| candidate.text)
+            .collect();
+        assert_eq!(columns, ["id", "name"]);
+    }
+
+    #[test]
+    fn complete_tables_of_a_database_qualifier() {
+        assert_eq!(candidates("select * from shop."), ["orders", "users"]);
+        assert_eq!(candidates("select * from logs."), ["events"]);
+        assert_eq!(candidates("select * from LOGS.ev"), ["events"]);
+    }
+
+    #[test]
+    fn complete_nothing_inside_strings() {
+        assert!(candidates("select 'ord").is_empty());
+    }
+}
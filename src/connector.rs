@@ -1,7 +1,10 @@
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use sqlx::{Column, MySql, MySqlConnection, Row, TypeInfo};
+use sqlx::{Column, Executor, MySql, MySqlConnection, Row, TypeInfo};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::formatter::format_duration;
 
 #[derive(Debug)]
 pub struct MySqlOutputTable {
@@ -27,29 +30,105 @@ pub struct MySqlRowsAffected {
 pub enum MySqlResult {
     Table(MySqlOutputTable),
     RowsAffected(MySqlRowsAffected),
+    DatabaseChanged(Option<String>),
 }
 
-pub async fn get_symbols(
+pub async fn get_current_database(
     connection: Arc<Mutex<MySqlConnection>>,
-) -> Result<Vec<String>, sqlx::Error> {
-    let mut symbols = Vec::new();
-    let tables = sqlx::query("SHOW TABLES")
-        .fetch_all(&mut *connection.lock().unwrap())
+) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query("SELECT DATABASE()")
+        .fetch_one(&mut *connection.lock().await)
         .await?;
-    for table in &tables {
-        symbols.push(table.try_get::<String, _>(0)?);
+    row.try_get::<Option<String>, _>(0)
+}
+
+/// Runs a query whose columns are all cast to CHAR, reading NULL as empty
+pub async fn fetch_strings(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    sql: &str,
+    binds: &[String],
+) -> Result<Vec<Vec<String>>, sqlx::Error> {
+    let mut query = sqlx::query(sql);
+    for bind in binds {
+        query = query.bind(bind);
     }
+    let rows = query.fetch_all(&mut *connection.lock().await).await?;
 
-    for table in &tables {
-        let columns = sqlx::query(format!("DESC {}", table.try_get::<String, _>(0)?).as_str())
-            .fetch_all(&mut *connection.lock().unwrap())
-            .await?;
-        for column in &columns {
-            symbols.push(column.try_get::<String, _>(0)?);
+    let mut values = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let mut row_values = Vec::with_capacity(row.len());
+        for i in 0..row.len() {
+            row_values.push(row.try_get::<Option<String>, _>(i)?.unwrap_or_default());
         }
+        values.push(row_values);
     }
+    Ok(values)
+}
+
+/// What `\s` reports about the server and this connection, as labelled values
+pub async fn server_status(
+    connection: &Arc<Mutex<MySqlConnection>>,
+) -> Result<Vec<(&'static str, String)>, sqlx::Error> {
+    let rows = fetch_strings(
+        connection,
+        "SELECT CAST(CONNECTION_ID() AS CHAR), CAST(CURRENT_USER() AS CHAR), \
+         CAST(DATABASE() AS CHAR), CAST(CONCAT_WS(' ', VERSION(), @@version_comment) AS CHAR), \
+         CAST(IF(@@autocommit, 'on', 'off') AS CHAR), CAST(@@character_set_server AS CHAR), \
+         CAST(@@character_set_database AS CHAR), CAST(@@character_set_client AS CHAR), \
+         CAST(@@character_set_connection AS CHAR)",
+        &[],
+    )
+    .await?;
+    let row = rows.into_iter().next().unwrap_or_default();
+    let value = |i: usize| row.get(i).cloned().unwrap_or_default();
 
-    Ok(symbols)
+    let variables: HashMap<String, String> = fetch_strings(
+        connection,
+        "SHOW SESSION STATUS WHERE Variable_name IN ('Ssl_cipher', 'Ssl_version', 'Uptime')",
+        &[],
+    )
+    .await?
+    .into_iter()
+    .filter_map(|row| Some((row.first()?.clone(), row.get(1)?.clone())))
+    .collect();
+    let ssl = match (variables.get("Ssl_cipher"), variables.get("Ssl_version")) {
+        (Some(cipher), Some(version)) if !cipher.is_empty() => {
+            format!("Cipher in use is {} ({})", cipher, version)
+        }
+        _ => "Not in use".to_string(),
+    };
+    let uptime = variables
+        .get("Uptime")
+        .and_then(|uptime| uptime.parse().ok())
+        .map(format_duration)
+        .unwrap_or_default();
+
+    // MariaDB tracks this per session for anyone, MySQL has no such variable
+    let transaction = fetch_strings(connection, "SELECT CAST(@@in_transaction AS CHAR)", &[])
+        .await
+        .ok()
+        .and_then(|rows| rows.into_iter().next()?.into_iter().next())
+        .map(|flag| if flag == "0" { "none" } else { "active" });
+
+    let mut entries = vec![
+        ("Connection id", value(0)),
+        ("Current database", value(2)),
+        ("Current user", value(1)),
+        ("SSL", ssl),
+        ("Server version", value(3)),
+        ("Autocommit", value(4)),
+    ];
+    if let Some(transaction) = transaction {
+        entries.push(("Transaction", transaction.to_string()));
+    }
+    entries.extend([
+        ("Server characterset", value(5)),
+        ("Db characterset", value(6)),
+        ("Client characterset", value(7)),
+        ("Conn. characterset", value(8)),
+        ("Uptime", uptime),
+    ]);
+    Ok(entries)
 }
 
 fn handle_result<T>(value: Result<Option<T>, sqlx::Error>) -> String
@@ -78,19 +157,25 @@ impl MySqlResult {
         match first_word.as_str() {
             "INSERT" | "UPDATE" | "DELETE" | "REPLACE" => {
                 let affected_rows = sqlx::query(query.as_str())
-                    .execute(&mut *connection.lock().unwrap())
+                    .execute(&mut *connection.lock().await)
                     .await?;
                 let result = MySqlResult::RowsAffected(MySqlRowsAffected {
                     affected_rows: affected_rows.rows_affected(),
                 });
                 return Ok(result);
             }
+            "USE" => {
+                // USE is not supported by the prepared statement protocol, so run it as raw SQL
+                connection.lock().await.execute(query.as_str()).await?;
+                let database = get_current_database(connection.clone()).await?;
+                return Ok(MySqlResult::DatabaseChanged(database));
+            }
             _ => {}
         }
 
         let mut result = MySqlOutputTable::new();
         let rows = sqlx::query(query.as_str())
-            .fetch_all(&mut *connection.lock().unwrap())
+            .fetch_all(&mut *connection.lock().await)
             .await?;
 
         if let Some(first_row) = rows.first() {
@@ -0,0 +1,456 @@
+use sqlx::MySqlConnection;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::connector::{fetch_strings, MySqlOutputTable};
+use crate::formatter::human_size;
+
+/// Databases that hold the server's own tables, left out of listings when no database is in use
+const SYSTEM_DATABASES: &str = "'mysql', 'information_schema', 'performance_schema', 'sys'";
+
+/// A titled result of one of the `\d` listings
+pub struct Listing {
+    pub title: String,
+    pub table: MySqlOutputTable,
+}
+
+/// Everything `\d table` shows about a table or view
+pub struct TableDescription {
+    pub database: String,
+    pub name: String,
+    pub is_view: bool,
+    pub engine: String,
+    pub comment: String,
+    pub columns: MySqlOutputTable,
+    pub indexes: Vec<String>,
+    pub foreign_keys: Vec<String>,
+    pub referenced_by: Vec<String>,
+}
+
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> MySqlOutputTable {
+    MySqlOutputTable {
+        headers: headers.iter().map(|h| h.to_string()).collect(),
+        values: rows
+            .into_iter()
+            .map(|row| {
+                headers
+                    .iter()
+                    .map(|h| h.to_string())
+                    .zip(row)
+                    .collect::<HashMap<_, _>>()
+            })
+            .collect(),
+    }
+}
+
+/// Splits a `[database.]name` pattern into its database and a LIKE pattern, where `*` and `?`
+/// are the wildcards
+pub fn parse_pattern(pattern: &str) -> (Option<String>, String) {
+    let pattern = pattern.replace('`', "");
+    let (database, name) = match pattern.split_once('.') {
+        Some((database, name)) => (Some(database.to_string()), name),
+        None => (None, pattern.as_str()),
+    };
+
+    let mut like = String::new();
+    for c in name.chars() {
+        match c {
+            '*' => like.push('%'),
+            '?' => like.push('_'),
+            '%' | '_' | '\\' => {
+                like.push('\\');
+                like.push(c);
+            }
+            _ => like.push(c),
+        }
+    }
+    if like.is_empty() {
+        like.push('%');
+    }
+    (database, like)
+}
+
+/// Restricts `column` to the given database, or to every user database when there is none
+fn database_condition(column: &str, database: Option<&str>, binds: &mut Vec<String>) -> String {
+    match database {
+        Some(database) => {
+            binds.push(database.to_string());
+            format!("{column} = ?")
+        }
+        None => format!("{column} NOT IN ({SYSTEM_DATABASES})"),
+    }
+}
+
+/// Runs a listing query whose first condition restricts the database and second the name
+async fn list(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    title: &str,
+    headers: &[&str],
+    sql: &str,
+    database_column: &str,
+    pattern: &str,
+    current_database: Option<&str>,
+) -> Result<Listing, sqlx::Error> {
+    let (database, like) = parse_pattern(pattern);
+    let mut binds = Vec::new();
+    let condition = database_condition(
+        database_column,
+        database.as_deref().or(current_database),
+        &mut binds,
+    );
+    binds.push(like);
+    let rows = fetch_strings(connection, &sql.replace("{database}", &condition), &binds).await?;
+    Ok(Listing {
+        title: title.to_string(),
+        table: table(headers, rows),
+    })
+}
+
+/// `\d` without arguments: tables and views
+pub async fn list_relations(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    pattern: &str,
+    current_database: Option<&str>,
+) -> Result<Listing, sqlx::Error> {
+    list(
+        connection,
+        "List of relations",
+        &["Database", "Name", "Type"],
+        "SELECT CAST(TABLE_SCHEMA AS CHAR), CAST(TABLE_NAME AS CHAR), \
+         CAST(IF(TABLE_TYPE = 'BASE TABLE', 'table', LOWER(TABLE_TYPE)) AS CHAR) \
+         FROM information_schema.TABLES WHERE {database} AND TABLE_NAME LIKE ? \
+         ORDER BY TABLE_SCHEMA, TABLE_NAME",
+        "TABLE_SCHEMA",
+        pattern,
+        current_database,
+    )
+    .await
+}
+
+pub async fn list_tables(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    pattern: &str,
+    current_database: Option<&str>,
+) -> Result<Listing, sqlx::Error> {
+    let mut listing = list(
+        connection,
+        "List of tables",
+        &["Database", "Name", "Engine", "Rows", "Size", "Comment"],
+        "SELECT CAST(TABLE_SCHEMA AS CHAR), CAST(TABLE_NAME AS CHAR), CAST(ENGINE AS CHAR), \
+         CAST(TABLE_ROWS AS CHAR), CAST(DATA_LENGTH + INDEX_LENGTH AS CHAR), \
+         CAST(TABLE_COMMENT AS CHAR) \
+         FROM information_schema.TABLES \
+         WHERE {database} AND TABLE_NAME LIKE ? AND TABLE_TYPE = 'BASE TABLE' \
+         ORDER BY TABLE_SCHEMA, TABLE_NAME",
+        "TABLE_SCHEMA",
+        pattern,
+        current_database,
+    )
+    .await?;
+    for row in &mut listing.table.values {
+        if let Some(size) = row.get_mut("Size") {
+            if let Ok(bytes) = size.parse() {
+                *size = human_size(bytes);
+            }
+        }
+    }
+    Ok(listing)
+}
+
+pub async fn list_views(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    pattern: &str,
+    current_database: Option<&str>,
+) -> Result<Listing, sqlx::Error> {
+    list(
+        connection,
+        "List of views",
+        &["Database", "Name", "Updatable", "Definer", "Security"],
+        "SELECT CAST(TABLE_SCHEMA AS CHAR), CAST(TABLE_NAME AS CHAR), CAST(IS_UPDATABLE AS CHAR), \
+         CAST(DEFINER AS CHAR), CAST(SECURITY_TYPE AS CHAR) \
+         FROM information_schema.VIEWS WHERE {database} AND TABLE_NAME LIKE ? \
+         ORDER BY TABLE_SCHEMA, TABLE_NAME",
+        "TABLE_SCHEMA",
+        pattern,
+        current_database,
+    )
+    .await
+}
+
+/// `\di`, where the pattern matches either the index or its table
+pub async fn list_indexes(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    pattern: &str,
+    current_database: Option<&str>,
+) -> Result<Listing, sqlx::Error> {
+    let (database, like) = parse_pattern(pattern);
+    let mut binds = Vec::new();
+    let condition = database_condition(
+        "TABLE_SCHEMA",
+        database.as_deref().or(current_database),
+        &mut binds,
+    );
+    binds.push(like.clone());
+    binds.push(like);
+    let rows = fetch_strings(
+        connection,
+        &format!(
+            "SELECT CAST(TABLE_SCHEMA AS CHAR), CAST(TABLE_NAME AS CHAR), CAST(INDEX_NAME AS CHAR), \
+             CAST(IF(MIN(NON_UNIQUE) = 0, 'yes', 'no') AS CHAR), CAST(INDEX_TYPE AS CHAR), \
+             CAST(GROUP_CONCAT(COLUMN_NAME ORDER BY SEQ_IN_INDEX SEPARATOR ', ') AS CHAR) \
+             FROM information_schema.STATISTICS \
+             WHERE {condition} AND (INDEX_NAME LIKE ? OR TABLE_NAME LIKE ?) \
+             GROUP BY TABLE_SCHEMA, TABLE_NAME, INDEX_NAME, INDEX_TYPE \
+             ORDER BY TABLE_SCHEMA, TABLE_NAME, INDEX_NAME"
+        ),
+        &binds,
+    )
+    .await?;
+    Ok(Listing {
+        title: "List of indexes".to_string(),
+        table: table(
+            &["Database", "Table", "Name", "Unique", "Type", "Columns"],
+            rows,
+        ),
+    })
+}
+
+pub async fn list_routines(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    pattern: &str,
+    current_database: Option<&str>,
+) -> Result<Listing, sqlx::Error> {
+    list(
+        connection,
+        "List of routines",
+        &[
+            "Database",
+            "Name",
+            "Type",
+            "Arguments",
+            "Returns",
+            "Comment",
+        ],
+        "SELECT CAST(r.ROUTINE_SCHEMA AS CHAR), CAST(r.ROUTINE_NAME AS CHAR), \
+         CAST(LOWER(r.ROUTINE_TYPE) AS CHAR), \
+         CAST((SELECT GROUP_CONCAT(CONCAT_WS(' ', p.PARAMETER_MODE, p.PARAMETER_NAME, \
+         p.DTD_IDENTIFIER) ORDER BY p.ORDINAL_POSITION SEPARATOR ', ') \
+         FROM information_schema.PARAMETERS p WHERE p.SPECIFIC_SCHEMA = r.ROUTINE_SCHEMA \
+         AND p.SPECIFIC_NAME = r.SPECIFIC_NAME AND p.ORDINAL_POSITION > 0) AS CHAR), \
+         CAST(r.DTD_IDENTIFIER AS CHAR), CAST(r.ROUTINE_COMMENT AS CHAR) \
+         FROM information_schema.ROUTINES r WHERE {database} AND r.ROUTINE_NAME LIKE ? \
+         ORDER BY r.ROUTINE_SCHEMA, r.ROUTINE_NAME",
+        "r.ROUTINE_SCHEMA",
+        pattern,
+        current_database,
+    )
+    .await
+}
+
+/// `\l` and `\dn`, which list every database whatever the current one is
+pub async fn list_databases(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    pattern: &str,
+) -> Result<Listing, sqlx::Error> {
+    let (_, like) = parse_pattern(pattern);
+    let rows = fetch_strings(
+        connection,
+        "SELECT CAST(s.SCHEMA_NAME AS CHAR), CAST(s.DEFAULT_CHARACTER_SET_NAME AS CHAR), \
+         CAST(s.DEFAULT_COLLATION_NAME AS CHAR), \
+         CAST((SELECT COUNT(*) FROM information_schema.TABLES t \
+         WHERE t.TABLE_SCHEMA = s.SCHEMA_NAME) AS CHAR) \
+         FROM information_schema.SCHEMATA s WHERE s.SCHEMA_NAME LIKE ? \
+         ORDER BY s.SCHEMA_NAME",
+        &[like],
+    )
+    .await?;
+    Ok(Listing {
+        title: "List of databases".to_string(),
+        table: table(&["Name", "Charset", "Collation", "Tables"], rows),
+    })
+}
+
+/// Foreign keys of a table, or the ones pointing at it when `referencing` is set
+async fn foreign_keys(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    database: &str,
+    name: &str,
+    referencing: bool,
+) -> Result<Vec<String>, sqlx::Error> {
+    let condition = if referencing {
+        "k.REFERENCED_TABLE_SCHEMA = ? AND k.REFERENCED_TABLE_NAME = ?"
+    } else {
+        "k.TABLE_SCHEMA = ? AND k.TABLE_NAME = ?"
+    };
+    let rows = fetch_strings(
+        connection,
+        &format!(
+            "SELECT CAST(k.TABLE_SCHEMA AS CHAR), CAST(k.TABLE_NAME AS CHAR), \
+             CAST(k.CONSTRAINT_NAME AS CHAR), \
+             CAST(GROUP_CONCAT(k.COLUMN_NAME ORDER BY k.ORDINAL_POSITION SEPARATOR ', ') AS CHAR), \
+             CAST(k.REFERENCED_TABLE_SCHEMA AS CHAR), CAST(k.REFERENCED_TABLE_NAME AS CHAR), \
+             CAST(GROUP_CONCAT(k.REFERENCED_COLUMN_NAME ORDER BY k.ORDINAL_POSITION \
+             SEPARATOR ', ') AS CHAR), \
+             CAST(r.UPDATE_RULE AS CHAR), CAST(r.DELETE_RULE AS CHAR) \
+             FROM information_schema.KEY_COLUMN_USAGE k \
+             JOIN information_schema.REFERENTIAL_CONSTRAINTS r \
+             ON r.CONSTRAINT_SCHEMA = k.CONSTRAINT_SCHEMA \
+             AND r.CONSTRAINT_NAME = k.CONSTRAINT_NAME AND r.TABLE_NAME = k.TABLE_NAME \
+             WHERE {condition} \
+             GROUP BY k.TABLE_SCHEMA, k.TABLE_NAME, k.CONSTRAINT_NAME, \
+             k.REFERENCED_TABLE_SCHEMA, k.REFERENCED_TABLE_NAME, r.UPDATE_RULE, r.DELETE_RULE \
+             ORDER BY k.TABLE_SCHEMA, k.TABLE_NAME, k.CONSTRAINT_NAME"
+        ),
+        &[database.to_string(), name.to_string()],
+    )
+    .await?;
+
+    let qualified = |schema: &str, table: &str| {
+        if schema == database {
+            table.to_string()
+        } else {
+            format!("{schema}.{table}")
+        }
+    };
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let mut line = format!(
+                "\"{}\" FOREIGN KEY ({}) REFERENCES {}({})",
+                row[2],
+                row[3],
+                qualified(&row[4], &row[5]),
+                row[6]
+            );
+            // Leave out the rules that only restrict, as MySQL does in SHOW CREATE TABLE
+            for (action, rule) in [("UPDATE", &row[7]), ("DELETE", &row[8])] {
+                if rule != "NO ACTION" && rule != "RESTRICT" {
+                    line.push_str(&format!(" ON {action} {rule}"));
+                }
+            }
+            if referencing {
+                format!("TABLE {} CONSTRAINT {}", qualified(&row[0], &row[1]), line)
+            } else {
+                line
+            }
+        })
+        .collect())
+}
+
+/// `\d table`, returning `None` when there is no such table or view
+pub async fn describe_table(
+    connection: &Arc<Mutex<MySqlConnection>>,
+    name: &str,
+    current_database: Option<&str>,
+) -> Result<Option<TableDescription>, sqlx::Error> {
+    let name = name.replace('`', "");
+    let (database, name) = match name.split_once('.') {
+        Some((database, name)) => (database.to_string(), name.to_string()),
+        None => match current_database {
+            Some(database) => (database.to_string(), name),
+            None => return Ok(None),
+        },
+    };
+    let binds = [database.clone(), name.clone()];
+
+    let info = fetch_strings(
+        connection,
+        "SELECT CAST(TABLE_TYPE AS CHAR), CAST(ENGINE AS CHAR), CAST(TABLE_COMMENT AS CHAR) \
+         FROM information_schema.TABLES WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?",
+        &binds,
+    )
+    .await?;
+    let Some(info) = info.into_iter().next() else {
+        return Ok(None);
+    };
+    let is_view = info[0] == "VIEW";
+
+    let columns = fetch_strings(
+        connection,
+        "SELECT CAST(COLUMN_NAME AS CHAR), CAST(COLUMN_TYPE AS CHAR), \
+         CAST(IF(IS_NULLABLE = 'NO', 'not null', '') AS CHAR), CAST(COLUMN_DEFAULT AS CHAR), \
+         CAST(COLUMN_KEY AS CHAR), CAST(EXTRA AS CHAR), CAST(COLUMN_COMMENT AS CHAR) \
+         FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ? \
+         ORDER BY ORDINAL_POSITION",
+        &binds,
+    )
+    .await?;
+
+    let indexes = fetch_strings(
+        connection,
+        "SELECT CAST(INDEX_NAME AS CHAR), CAST(MIN(NON_UNIQUE) AS CHAR), CAST(INDEX_TYPE AS CHAR), \
+         CAST(GROUP_CONCAT(COLUMN_NAME ORDER BY SEQ_IN_INDEX SEPARATOR ', ') AS CHAR) \
+         FROM information_schema.STATISTICS WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ? \
+         GROUP BY INDEX_NAME, INDEX_TYPE ORDER BY INDEX_NAME = 'PRIMARY' DESC, INDEX_NAME",
+        &binds,
+    )
+    .await?
+    .into_iter()
+    .map(|row| {
+        let kind = if row[0] == "PRIMARY" {
+            "PRIMARY KEY, "
+        } else if row[1] == "0" {
+            "UNIQUE, "
+        } else {
+            ""
+        };
+        format!("\"{}\" {}{} ({})", row[0], kind, row[2], row[3])
+    })
+    .collect();
+
+    Ok(Some(TableDescription {
+        foreign_keys: foreign_keys(connection, &database, &name, false).await?,
+        referenced_by: foreign_keys(connection, &database, &name, true).await?,
+        database,
+        name,
+        is_view,
+        engine: info[1].clone(),
+        comment: info[2].clone(),
+        columns: table(
+            &[
+                "Column", "Type", "Nullable", "Default", "Key", "Extra", "Comment",
+            ],
+            columns,
+        ),
+        indexes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(database: Option<&str>, like: &str) -> (Option<String>, String) {
+        (database.map(str::to_string), like.to_string())
+    }
+
+    #[test]
+    fn parse_pattern_translates_wildcards() {
+        assert_eq!(parse_pattern("user*"), pattern(None, "user%"));
+        assert_eq!(parse_pattern("t?"), pattern(None, "t_"));
+        assert_eq!(parse_pattern("*log?"), pattern(None, "%log_"));
+        assert_eq!(parse_pattern(""), pattern(None, "%"));
+    }
+
+    #[test]
+    fn parse_pattern_escapes_like_wildcards() {
+        assert_eq!(parse_pattern("user_roles"), pattern(None, "user\\_roles"));
+        assert_eq!(parse_pattern("100%"), pattern(None, "100\\%"));
+        assert_eq!(parse_pattern("a\\b"), pattern(None, "a\\\\b"));
+    }
+
+    #[test]
+    fn parse_pattern_splits_the_database() {
+        assert_eq!(
+            parse_pattern("shop.orders"),
+            pattern(Some("shop"), "orders")
+        );
+        assert_eq!(parse_pattern("shop.*"), pattern(Some("shop"), "%"));
+        assert_eq!(parse_pattern("shop."), pattern(Some("shop"), "%"));
+        assert_eq!(
+            parse_pattern("`my_db`.`order_items`"),
+            pattern(Some("my_db"), "order\\_items")
+        );
+    }
+}
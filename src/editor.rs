@@ -0,0 +1,1179 @@
+use console::{measure_text_width, style, Key, Term};
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::buffer::{Buffer, KillRing};
+use crate::completion::Completion;
+use crate::history::{History, HistorySearch};
+use crate::lexer::strip_backslash_command;
+use crate::menu::CompletionMenu;
+use crate::signal::ignore_interrupts;
+use crate::trie::Trie;
+use crate::vi::ViKeymap;
+
+/// What the line editor needs to know about the language being edited
+pub trait Helper {
+    fn complete(&self, line: &str, cursor: usize) -> Completion;
+    /// Short hint shown after the line, like the signature of the function being called
+    fn hint(&self, line: &str, cursor: usize) -> Option<String>;
+    /// Whether Enter should submit the line
+    fn is_complete(&self, line: &str) -> bool;
+    /// The line with ANSI styles applied, taking up the same width as the line itself, with
+    /// the bracket at `cursor` and its partner marked
+    fn highlight(&self, line: &str, cursor: Option<usize>) -> String;
+    /// Why a complete line can't be submitted yet, and where
+    fn check(&self, line: &str) -> Option<(usize, String)>;
+    /// How many statements the line holds
+    fn statement_count(&self, line: &str) -> usize;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    SelfInsert(char),
+    MoveLeft,
+    MoveRight,
+    MoveStart,
+    MoveEnd,
+    MoveWordLeft,
+    MoveWordRight,
+    DeleteBackward,
+    DeleteForward,
+    DeleteForwardOrEof,
+    KillToEnd,
+    KillToStart,
+    KillBigWordBackward,
+    KillWordBackward,
+    KillWordForward,
+    Yank,
+    YankPop,
+    Undo,
+    Transpose,
+    UpcaseWord,
+    DowncaseWord,
+    CapitalizeWord,
+    HistoryPrevious,
+    HistoryNext,
+    ReverseSearch,
+    Complete,
+    Accept,
+    ClearScreen,
+    Cancel,
+    /// Moves the cursor to a byte offset
+    MoveTo(usize),
+    /// Removes a byte range into the kill ring
+    Kill(usize, usize),
+    /// Copies a byte range into the kill ring
+    Copy(usize, usize),
+    /// Inserts the last killed text, vi style, leaving the cursor on its last character
+    Paste {
+        after: bool,
+    },
+    /// Replaces every character in a byte range with the given one
+    Replace(usize, usize, char),
+    ToggleCase(usize, usize),
+    /// Edits the line, or the last statement when it's empty, in an external editor
+    EditExternally,
+    /// Replays the keys of the last vi change
+    RepeatChange,
+}
+
+impl Command {
+    fn is_kill(&self) -> bool {
+        matches!(
+            self,
+            Command::KillToEnd
+                | Command::KillToStart
+                | Command::KillBigWordBackward
+                | Command::KillWordBackward
+                | Command::KillWordForward
+        )
+    }
+
+    fn is_edit(&self) -> bool {
+        !matches!(
+            self,
+            Command::MoveLeft
+                | Command::MoveRight
+                | Command::MoveStart
+                | Command::MoveEnd
+                | Command::MoveWordLeft
+                | Command::MoveWordRight
+                | Command::Undo
+                | Command::ReverseSearch
+                | Command::Complete
+                | Command::Accept
+                | Command::ClearScreen
+                | Command::Cancel
+                | Command::MoveTo(_)
+                | Command::Copy(_, _)
+                | Command::RepeatChange
+        )
+    }
+}
+
+/// Emacs key bindings, as in readline's default mode
+#[derive(Debug, Default)]
+pub struct EmacsKeymap {
+    /// Set after Ctrl-X, which prefixes the next key
+    ctrl_x: bool,
+}
+
+impl EmacsKeymap {
+    pub fn command(&mut self, key: &Key) -> Option<Command> {
+        if std::mem::take(&mut self.ctrl_x) {
+            return match key {
+                // ctrl-x ctrl-u
+                Key::Char('\u{15}') => Some(Command::Undo),
+                // ctrl-x ctrl-e, which console reports as End
+                Key::End => Some(Command::EditExternally),
+                _ => None,
+            };
+        }
+
+        let command = match key {
+            Key::Char('\u{18}') => {
+                self.ctrl_x = true;
+                return None;
+            }
+            Key::Char(c) if !c.is_control() => Command::SelfInsert(*c),
+            Key::ArrowLeft | Key::Char('\u{2}') => Command::MoveLeft,
+            Key::ArrowRight | Key::Char('\u{6}') => Command::MoveRight,
+            // console reports ctrl-a and ctrl-e as Home and End
+            Key::Home => Command::MoveStart,
+            Key::End => Command::MoveEnd,
+            Key::Backspace => Command::DeleteBackward,
+            Key::Del => Command::DeleteForward,
+            Key::Char('\u{4}') => Command::DeleteForwardOrEof,
+            Key::Char('\u{b}') => Command::KillToEnd,
+            Key::Char('\u{15}') => Command::KillToStart,
+            Key::Char('\u{17}') => Command::KillBigWordBackward,
+            Key::Char('\u{19}') => Command::Yank,
+            Key::Char('\u{1f}') => Command::Undo,
+            Key::Char('\u{14}') => Command::Transpose,
+            Key::ArrowUp | Key::Char('\u{10}') => Command::HistoryPrevious,
+            Key::ArrowDown | Key::Char('\u{e}') => Command::HistoryNext,
+            Key::Char('\u{12}') => Command::ReverseSearch,
+            Key::Tab => Command::Complete,
+            Key::Enter => Command::Accept,
+            Key::Char('\u{c}') => Command::ClearScreen,
+            Key::Char('\u{7}') | Key::Escape => Command::Cancel,
+            // Alt-<key> arrives as an escape followed by the key
+            Key::UnknownEscSeq(sequence) => match sequence.as_slice() {
+                ['b'] => Command::MoveWordLeft,
+                ['f'] => Command::MoveWordRight,
+                ['d'] => Command::KillWordForward,
+                ['\u{7f}'] | ['\u{8}'] => Command::KillWordBackward,
+                ['y'] => Command::YankPop,
+                ['u'] => Command::UpcaseWord,
+                ['l'] => Command::DowncaseWord,
+                ['c'] => Command::CapitalizeWord,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditingMode {
+    Emacs,
+    Vi,
+}
+
+impl std::str::FromStr for EditingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "emacs" => Ok(EditingMode::Emacs),
+            "vi" => Ok(EditingMode::Vi),
+            _ => Err(format!(
+                "Unknown editing mode: {} (expected emacs or vi)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for EditingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditingMode::Emacs => write!(f, "emacs"),
+            EditingMode::Vi => write!(f, "vi"),
+        }
+    }
+}
+
+impl EditingMode {
+    /// Editing mode chosen with `set editing-mode` in the readline init file, if any
+    pub fn from_inputrc() -> Option<Self> {
+        let path = match std::env::var_os("INPUTRC") {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::path::Path::new(&std::env::var_os("HOME")?).join(".inputrc"),
+        };
+        let contents = std::fs::read_to_string(path).ok()?;
+        contents.lines().rev().find_map(|line| {
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["set", "editing-mode", mode] => mode.parse().ok(),
+                _ => None,
+            }
+        })
+    }
+}
+
+enum Keymap {
+    Emacs(EmacsKeymap),
+    Vi(ViKeymap),
+}
+
+impl Keymap {
+    fn commands(&mut self, key: &Key, buffer: &Buffer) -> Vec<Command> {
+        match self {
+            Keymap::Emacs(keymap) => keymap.command(key).into_iter().collect(),
+            Keymap::Vi(keymap) => keymap.commands(key, buffer),
+        }
+    }
+
+    fn indicator(&self) -> &'static str {
+        match self {
+            Keymap::Emacs(_) => "",
+            Keymap::Vi(keymap) => keymap.indicator(),
+        }
+    }
+}
+
+enum Outcome {
+    Continue,
+    Submit,
+    Eof,
+}
+
+/// Editing state of the line currently being read
+struct State {
+    buffer: Buffer,
+    trie: Trie,
+    /// History entries matching what was typed before browsing, best ranked last
+    commands: Vec<String>,
+    command_offset: usize,
+    menu: Option<CompletionMenu>,
+    search: Option<HistorySearch>,
+    last_command: Option<Command>,
+    /// Range of the text inserted by the last yank
+    yanked: Option<(usize, usize)>,
+    /// Shown under the line until the next key, like why Enter refused it
+    message: Option<String>,
+}
+
+impl State {
+    fn new(trie: Trie) -> Self {
+        let commands = trie.search_ignore_case("");
+        Self {
+            buffer: Buffer::new(),
+            trie,
+            commands,
+            command_offset: 0,
+            menu: None,
+            search: None,
+            last_command: None,
+            yanked: None,
+            message: None,
+        }
+    }
+
+    fn update_commands(&mut self) {
+        self.commands = self.trie.search_ignore_case(&self.buffer.text);
+        self.command_offset = 0;
+    }
+
+    /// The rest of the best ranked history entry starting with the line, matching case so what
+    /// was typed is kept as is when the suggestion is accepted
+    fn autosuggestion(&self) -> Option<&str> {
+        let text = &self.buffer.text;
+        if text.is_empty() || self.command_offset != 0 || self.buffer.cursor != text.len() {
+            return None;
+        }
+        self.commands
+            .iter()
+            .rev()
+            .find(|command| command.len() > text.len() && command.starts_with(text.as_str()))
+            .map(|command| &command[text.len()..])
+    }
+}
+
+const TAB: &str = "    ";
+
+/// Row and column the terminal cursor reaches after printing `text` from the start of a row,
+/// where filling a row leaves it past the last column until the next character wraps it
+fn end_position(text: &str, columns: usize) -> (usize, usize) {
+    let (mut row, mut column) = (0, 0);
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            row += 1;
+            column = 0;
+        }
+        let width = column + measure_text_width(line);
+        if width > 0 {
+            row += (width - 1) / columns;
+            column = (width - 1) % columns + 1;
+        }
+    }
+    (row, column)
+}
+
+/// Creates an empty file only the user can read, under a name other users can't guess ahead of
+/// time to plant a symlink there
+fn create_temp_file() -> std::io::Result<(PathBuf, File)> {
+    let mut attempts = 0;
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        let name = format!("oxisql-{}-{:016x}.sql", std::process::id(), hasher.finish());
+        let path = std::env::temp_dir().join(name);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && attempts < 100 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Opens `text` in `$VISUAL` or `$EDITOR` through a temporary file and returns what was saved
+pub fn edit_externally(text: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let (path, mut file) = create_temp_file()?;
+    let written = writeln!(file, "{}", text);
+    drop(file);
+    if let Err(e) = written {
+        std::fs::remove_file(&path)?;
+        return Err(e.into());
+    }
+
+    // Run through the shell so editors configured with arguments, like `code -w`, work
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let edited = std::fs::read_to_string(&path);
+    std::fs::remove_file(&path)?;
+
+    let status = status?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status).into());
+    }
+    Ok(edited?.trim_end().to_string())
+}
+
+/// Asks the terminal to mark pasted text, so it isn't taken for typed keys
+fn set_bracketed_paste(term: &Term, enabled: bool) {
+    if term.is_term() {
+        let mode = if enabled {
+            "\x1b[?2004h"
+        } else {
+            "\x1b[?2004l"
+        };
+        // Nothing useful to do if the terminal is gone
+        let _ = term.write_str(mode);
+    }
+}
+
+/// Keeps bracketed paste on until dropped, so no way out of `read_line` leaves it on for the
+/// programs run after oxisql
+struct BracketedPaste {
+    term: Term,
+}
+
+impl BracketedPaste {
+    fn enable(term: &Term) -> Self {
+        set_bracketed_paste(term, true);
+        Self { term: term.clone() }
+    }
+}
+
+impl Drop for BracketedPaste {
+    fn drop(&mut self) {
+        set_bracketed_paste(&self.term, false);
+    }
+}
+
+pub struct LineEditor {
+    term: Term,
+    prompt: String,
+    keymap: Keymap,
+    kill_ring: KillRing,
+    /// Row of the cursor below the first row of the prompt, as left by the last redraw
+    cursor_row: usize,
+    /// What the last redraw printed, so moving the cursor alone doesn't print it again
+    drawn: Option<(String, Vec<String>)>,
+    /// Whether typing an opening bracket or quote also inserts its closing half
+    auto_pairs: bool,
+}
+
+impl LineEditor {
+    pub fn new(prompt: &str) -> Self {
+        Self {
+            term: Term::stdout(),
+            prompt: prompt.to_string(),
+            keymap: Keymap::Emacs(EmacsKeymap::default()),
+            kill_ring: KillRing::default(),
+            cursor_row: 0,
+            drawn: None,
+            auto_pairs: false,
+        }
+    }
+
+    pub fn auto_pairs(&self) -> bool {
+        self.auto_pairs
+    }
+
+    pub fn set_auto_pairs(&mut self, enabled: bool) {
+        self.auto_pairs = enabled;
+    }
+
+    pub fn editing_mode(&self) -> EditingMode {
+        match self.keymap {
+            Keymap::Emacs(_) => EditingMode::Emacs,
+            Keymap::Vi(_) => EditingMode::Vi,
+        }
+    }
+
+    pub fn set_editing_mode(&mut self, mode: EditingMode) {
+        self.keymap = match mode {
+            EditingMode::Emacs => Keymap::Emacs(EmacsKeymap::default()),
+            EditingMode::Vi => Keymap::Vi(ViKeymap::default()),
+        };
+    }
+
+    /// Reads one line, returning `None` at end of input
+    pub fn read_line(&mut self, helper: &dyn Helper, history: &mut History) -> Option<String> {
+        let mut state = State::new(history.trie());
+        self.forget_screen();
+        if let Keymap::Vi(keymap) = &mut self.keymap {
+            keymap.reset();
+        }
+        let _interrupts = ignore_interrupts();
+        let _paste = BracketedPaste::enable(&self.term);
+        self.render(&state, helper);
+
+        loop {
+            let key = match self.term.read_key() {
+                Ok(key) => key,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {
+                    // Like readline, Ctrl-C abandons the line for a fresh prompt
+                    self.render_line(&helper.highlight(&state.buffer.text, None));
+                    self.term.write_line("^C").unwrap();
+                    state = State::new(history.trie());
+                    self.forget_screen();
+                    if let Keymap::Vi(keymap) = &mut self.keymap {
+                        keymap.reset();
+                    }
+                    self.render(&state, helper);
+                    continue;
+                }
+                Err(_) => {
+                    self.term.write_line("").unwrap();
+                    return None;
+                }
+            };
+            state.message = None;
+
+            let commands = if key == Key::UnknownEscSeq(vec!['[', '2', '0']) {
+                // The start of ESC [ 2 0 0 ~, which begins a bracketed paste
+                match self.read_paste() {
+                    Some(text) if self.paste(&text, &mut state, helper) => vec![Command::Accept],
+                    _ => Vec::new(),
+                }
+            } else if (state.search.is_some() && self.handle_search(&key, &mut state, history))
+                || (state.menu.is_some() && self.handle_menu(&key, &mut state))
+            {
+                // Searching and the completion menu handled the key themselves
+                Vec::new()
+            } else {
+                self.keymap.commands(&key, &state.buffer)
+            };
+
+            for command in commands {
+                match self.execute(command, &mut state, helper, history) {
+                    Outcome::Continue => {}
+                    Outcome::Submit => {
+                        self.render_line(&helper.highlight(&state.buffer.text, None));
+                        self.term.write_line("").unwrap();
+                        return Some(state.buffer.text);
+                    }
+                    Outcome::Eof => {
+                        self.term.write_line("").unwrap();
+                        return None;
+                    }
+                }
+            }
+            self.keep_cursor_on_character(&mut state);
+            self.render(&state, helper);
+        }
+    }
+
+    /// Vi normal mode keeps the cursor on a character
+    fn keep_cursor_on_character(&self, state: &mut State) {
+        if matches!(&self.keymap, Keymap::Vi(keymap) if keymap.is_normal())
+            && state.buffer.cursor == state.buffer.text.len()
+        {
+            state.buffer.move_left();
+        }
+    }
+
+    /// Reads the rest of a bracketed paste after the ESC [ 2 0 that console reports as one key
+    fn read_paste(&self) -> Option<String> {
+        if self.term.read_key().ok()? != Key::Char('0')
+            || self.term.read_key().ok()? != Key::Char('~')
+        {
+            return None;
+        }
+
+        let mut text = String::new();
+        loop {
+            match self.term.read_key().ok()? {
+                // ESC [ 2 0 1 ~ ends the paste
+                Key::UnknownEscSeq(sequence) if sequence == ['[', '2', '0'] => {
+                    self.term.read_key().ok()?;
+                    self.term.read_key().ok()?;
+                    return Some(text);
+                }
+                Key::Char(c) => text.push(c),
+                Key::Enter => text.push('\n'),
+                Key::Tab => text.push('\t'),
+                _ => {}
+            }
+        }
+    }
+
+    /// Inserts pasted text as a single change, returning whether the user chose to run it
+    /// right away as a batch of statements
+    fn paste(&mut self, text: &str, state: &mut State, helper: &dyn Helper) -> bool {
+        state.buffer.checkpoint();
+        state.buffer.insert(text);
+        state.last_command = None;
+        state.update_commands();
+
+        let count = helper.statement_count(&state.buffer.text);
+        if count < 2 || !helper.is_complete(&state.buffer.text) {
+            return false;
+        }
+        state.message = Some(
+            style(format!(
+                "[?] Run the {} pasted statements now? [y/N]",
+                count
+            ))
+            .yellow()
+            .to_string(),
+        );
+        self.render(state, helper);
+        state.message = None;
+        matches!(self.term.read_key(), Ok(Key::Char('y' | 'Y')))
+    }
+
+    /// Handles a key while searching history, returning false when the key accepted the
+    /// match and should be handled as usual
+    fn handle_search(&mut self, key: &Key, state: &mut State, history: &mut History) -> bool {
+        let Some(search) = state.search.as_mut() else {
+            return false;
+        };
+        match key {
+            // ctrl-r
+            Key::Char('\u{12}') => search.older(),
+            // ctrl-g
+            Key::Char('\u{7}') | Key::Escape => state.search = None,
+            Key::Backspace => search.pop(),
+            Key::Del => {
+                if let Some(statement) = search.remove_current() {
+                    if let Err(e) = history.delete(&statement) {
+                        eprintln!("[-] Could not delete history entry: {}", e);
+                    }
+                    state.trie = history.trie();
+                    state.update_commands();
+                }
+            }
+            Key::Char(c) if !c.is_control() => search.push(*c),
+            _ => {
+                if let Some(found) = search.current() {
+                    state.buffer.checkpoint();
+                    state.buffer.set(found);
+                    state.update_commands();
+                }
+                state.search = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Handles a key while the completion menu is open, returning false when the key closed
+    /// the menu and should be handled as usual
+    fn handle_menu(&mut self, key: &Key, state: &mut State) -> bool {
+        let Some(menu) = state.menu.as_mut() else {
+            return false;
+        };
+        match key {
+            Key::Tab | Key::ArrowDown => menu.next(),
+            Key::BackTab | Key::ArrowUp => menu.previous(),
+            Key::Enter => {
+                let text = menu.selected().text.clone();
+                state.buffer.checkpoint();
+                state.buffer.replace_before_cursor(menu.start, &text);
+                state.menu = None;
+                state.update_commands();
+            }
+            Key::Escape | Key::Char('\u{7}') => state.menu = None,
+            _ => {
+                state.menu = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn execute(
+        &mut self,
+        command: Command,
+        state: &mut State,
+        helper: &dyn Helper,
+        history: &History,
+    ) -> Outcome {
+        let last_command = state.last_command.replace(command);
+        // Typing a run of characters is undone as one change
+        let typing = matches!(command, Command::SelfInsert(_))
+            && matches!(last_command, Some(Command::SelfInsert(_)));
+        if command.is_edit() && !typing {
+            state.buffer.checkpoint();
+        }
+        let append_kill = last_command.is_some_and(|c| c.is_kill());
+
+        let buffer = &mut state.buffer;
+        match command {
+            Command::SelfInsert(c) if self.auto_pairs => buffer.insert_paired(c),
+            Command::SelfInsert(c) => buffer.insert(c.encode_utf8(&mut [0; 4])),
+            Command::MoveLeft => buffer.move_left(),
+            Command::MoveRight | Command::MoveEnd => {
+                let at_end = buffer.cursor == buffer.text.len();
+                match state.autosuggestion().map(String::from) {
+                    Some(suggestion) if at_end => {
+                        state.buffer.insert(&suggestion);
+                        state.update_commands();
+                    }
+                    _ if command == Command::MoveEnd => state.buffer.move_end(),
+                    _ => state.buffer.move_right(),
+                }
+            }
+            Command::MoveStart => buffer.move_start(),
+            Command::MoveWordLeft => buffer.move_word_left(),
+            Command::MoveWordRight => buffer.move_word_right(),
+            Command::DeleteBackward if self.auto_pairs => buffer.delete_backward_paired(),
+            Command::DeleteBackward => buffer.delete_backward(),
+            Command::DeleteForward => buffer.delete_forward(),
+            Command::DeleteForwardOrEof => {
+                if buffer.is_empty() {
+                    return Outcome::Eof;
+                }
+                buffer.delete_forward();
+            }
+            Command::KillToEnd => {
+                let killed = buffer.kill_to_end();
+                self.kill_ring.kill(killed, append_kill, false);
+            }
+            Command::KillToStart => {
+                let killed = buffer.kill_to_start();
+                self.kill_ring.kill(killed, append_kill, true);
+            }
+            Command::KillBigWordBackward => {
+                let killed = buffer.kill_big_word_backward();
+                self.kill_ring.kill(killed, append_kill, true);
+            }
+            Command::KillWordBackward => {
+                let killed = buffer.kill_word_backward();
+                self.kill_ring.kill(killed, append_kill, true);
+            }
+            Command::KillWordForward => {
+                let killed = buffer.kill_word_forward();
+                self.kill_ring.kill(killed, append_kill, false);
+            }
+            Command::Yank => {
+                if let Some(text) = self.kill_ring.yank() {
+                    let start = buffer.cursor;
+                    buffer.insert(text);
+                    state.yanked = Some((start, buffer.cursor));
+                }
+            }
+            Command::YankPop => {
+                let yanking = matches!(last_command, Some(Command::Yank | Command::YankPop));
+                if let (true, Some((start, end))) = (yanking, state.yanked) {
+                    if let Some(text) = self.kill_ring.rotate() {
+                        buffer.remove(start, end);
+                        buffer.cursor = start;
+                        buffer.insert(text);
+                        state.yanked = Some((start, buffer.cursor));
+                    }
+                } else {
+                    // Only valid right after a yank
+                    state.last_command = last_command;
+                }
+            }
+            Command::Undo => buffer.undo(),
+            Command::Transpose => buffer.transpose(),
+            Command::UpcaseWord => buffer.change_word(|word| word.to_uppercase()),
+            Command::DowncaseWord => buffer.change_word(|word| word.to_lowercase()),
+            Command::CapitalizeWord => buffer.change_word(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect(),
+                    None => String::new(),
+                }
+            }),
+            Command::HistoryPrevious => {
+                if state.commands.is_empty() {
+                    return Outcome::Continue;
+                }
+                if state.command_offset != state.commands.len() {
+                    state.command_offset += 1;
+                }
+                let found = &state.commands[state.commands.len() - state.command_offset];
+                state.buffer.set(found);
+                return Outcome::Continue;
+            }
+            Command::HistoryNext => {
+                if state.command_offset == 0 {
+                    return Outcome::Continue;
+                }
+                state.command_offset -= 1;
+                if state.command_offset == 0 {
+                    state.buffer.set("");
+                    state.update_commands();
+                } else {
+                    let found = &state.commands[state.commands.len() - state.command_offset];
+                    state.buffer.set(found);
+                }
+                return Outcome::Continue;
+            }
+            Command::ReverseSearch => {
+                state.search = Some(HistorySearch::new(history.ranked()));
+                return Outcome::Continue;
+            }
+            Command::Complete => {
+                self.complete(state, helper);
+                return Outcome::Continue;
+            }
+            Command::Accept => {
+                if let Some(text) = strip_backslash_command(&buffer.text, "\\e") {
+                    let text = text.trim_end().to_string();
+                    buffer.set(&text);
+                    self.edit_externally(state, history);
+                    return Outcome::Continue;
+                }
+                if helper.is_complete(&buffer.text) {
+                    if let Some((position, problem)) = helper.check(&buffer.text) {
+                        buffer.cursor = position;
+                        state.message = Some(style(format!("[-] {}", problem)).red().to_string());
+                        return Outcome::Continue;
+                    }
+                    return Outcome::Submit;
+                }
+                return Outcome::Continue;
+            }
+            Command::EditExternally => {
+                self.edit_externally(state, history);
+                return Outcome::Continue;
+            }
+            Command::ClearScreen => {
+                self.term.clear_screen().unwrap();
+                self.forget_screen();
+                return Outcome::Continue;
+            }
+            Command::Cancel => return Outcome::Continue,
+            Command::RepeatChange => {
+                let Keymap::Vi(keymap) = &self.keymap else {
+                    return Outcome::Continue;
+                };
+                for key in keymap.last_change() {
+                    for command in self.keymap.commands(&key, &state.buffer) {
+                        self.execute(command, state, helper, history);
+                    }
+                }
+                return Outcome::Continue;
+            }
+            Command::MoveTo(position) => buffer.cursor = position,
+            Command::Kill(start, end) => {
+                let killed = buffer.remove(start, end);
+                buffer.cursor = start.min(buffer.text.len());
+                self.kill_ring.kill(killed, false, false);
+            }
+            Command::Copy(start, end) => {
+                self.kill_ring
+                    .kill(buffer.text[start..end].to_string(), false, false);
+            }
+            Command::Paste { after } => {
+                if let Some(text) = self.kill_ring.yank() {
+                    if after {
+                        buffer.move_right();
+                    }
+                    buffer.insert(text);
+                    buffer.move_left();
+                }
+            }
+            Command::Replace(start, end, c) => {
+                let replaced: String = buffer.text[start..end].graphemes(true).map(|_| c).collect();
+                buffer.text.replace_range(start..end, &replaced);
+                buffer.cursor = start + replaced.len();
+                buffer.move_left();
+            }
+            Command::ToggleCase(start, end) => {
+                let toggled: String = buffer.text[start..end]
+                    .chars()
+                    .flat_map(|c| {
+                        if c.is_uppercase() {
+                            c.to_lowercase().collect::<Vec<_>>()
+                        } else {
+                            c.to_uppercase().collect()
+                        }
+                    })
+                    .collect();
+                buffer.text.replace_range(start..end, &toggled);
+                buffer.cursor = start + toggled.len();
+            }
+        }
+
+        if command.is_edit() {
+            state.update_commands();
+        }
+        Outcome::Continue
+    }
+
+    /// Replaces the line with what the user saves in their editor, leaving it for review
+    fn edit_externally(&mut self, state: &mut State, history: &History) {
+        let text = if state.buffer.is_empty() {
+            history
+                .entries
+                .last()
+                .map(|entry| entry.statement.clone())
+                .unwrap_or_default()
+        } else {
+            state.buffer.text.clone()
+        };
+
+        // Leave the prompt on screen and hand the terminal over below it
+        self.render_line(&state.buffer.text);
+        self.term.write_line("").unwrap();
+        self.forget_screen();
+
+        set_bracketed_paste(&self.term, false);
+        let edited = edit_externally(&text);
+        set_bracketed_paste(&self.term, true);
+        match edited {
+            Ok(edited) => {
+                state.buffer.checkpoint();
+                state.buffer.set(&edited);
+                state.update_commands();
+            }
+            Err(e) => eprintln!("[-] Could not edit statement: {}", e),
+        }
+    }
+
+    fn complete(&mut self, state: &mut State, helper: &dyn Helper) {
+        let buffer = &mut state.buffer;
+        let completion = helper.complete(&buffer.text, buffer.cursor);
+        let word = &buffer.text[completion.start..buffer.cursor];
+        let stem = completion.common_prefix();
+
+        if completion.candidates.len() == 1 {
+            let text = completion.candidates[0].text.clone();
+            buffer.checkpoint();
+            buffer.replace_before_cursor(completion.start, &text);
+        } else if stem.len() > word.len() && stem.to_lowercase().starts_with(&word.to_lowercase()) {
+            // All candidates share a longer stem, so complete up to it first
+            let stem = stem.to_string();
+            buffer.checkpoint();
+            buffer.replace_before_cursor(completion.start, &stem);
+        } else if !completion.candidates.is_empty() {
+            state.menu = Some(CompletionMenu::new(completion));
+            return;
+        }
+        state.update_commands();
+    }
+
+    /// The prompt, behind the vi mode indicator
+    fn prompt(&self) -> String {
+        format!("{}{}", self.keymap.indicator(), self.prompt)
+    }
+
+    /// Redraws `line` from the first row of the prompt, with `below` under it, and leaves the
+    /// cursor where printing `before_cursor` would, following where the terminal wraps
+    fn draw(&mut self, line: &str, before_cursor: &str, below: &[String]) {
+        // Tabs would jump to the terminal's tab stops, so give them a fixed width
+        let line = &line.replace('\t', TAB);
+        let before_cursor = &before_cursor.replace('\t', TAB);
+        let columns = (self.term.size().1 as usize).max(1);
+        let (mut end_row, _) = end_position(line, columns);
+        let (mut cursor_row, mut cursor_column) = end_position(before_cursor, columns);
+        if cursor_column == columns {
+            cursor_row += 1;
+            cursor_column = 0;
+        }
+
+        let unchanged = self
+            .drawn
+            .as_ref()
+            .is_some_and(|(drawn, drawn_below)| drawn == line && drawn_below == below);
+        if unchanged && cursor_row <= end_row {
+            if cursor_row < self.cursor_row {
+                self.term
+                    .move_cursor_up(self.cursor_row - cursor_row)
+                    .unwrap();
+            } else {
+                self.term
+                    .move_cursor_down(cursor_row - self.cursor_row)
+                    .unwrap();
+            }
+            self.term.write_str("\r").unwrap();
+            self.term.move_cursor_right(cursor_column).unwrap();
+            self.cursor_row = cursor_row;
+            return;
+        }
+        self.drawn = Some((line.to_string(), below.to_vec()));
+
+        self.term.move_cursor_up(self.cursor_row).unwrap();
+        self.term.clear_to_end_of_screen().unwrap();
+        self.term.write_str(line).unwrap();
+        if cursor_row > end_row {
+            // The cursor sits at the start of a row the line doesn't reach yet
+            self.term.write_str("\n").unwrap();
+            end_row = cursor_row;
+        }
+        for row in below {
+            self.term.write_str(format!("\n{row}").as_str()).unwrap();
+        }
+
+        self.term
+            .move_cursor_up(below.len() + end_row - cursor_row)
+            .unwrap();
+        self.term.write_str("\r").unwrap();
+        self.term.move_cursor_right(cursor_column).unwrap();
+        self.cursor_row = cursor_row;
+    }
+
+    /// Starts drawing afresh from the row the cursor is on
+    fn forget_screen(&mut self) {
+        self.cursor_row = 0;
+        self.drawn = None;
+    }
+
+    /// Draws the prompt and line alone, as it should stay in the scrollback
+    fn render_line(&mut self, text: &str) {
+        let line = format!("{}{}", self.prompt(), text);
+        self.draw(&line, &line, &[]);
+    }
+
+    fn render(&mut self, state: &State, helper: &dyn Helper) {
+        if let Some(search) = &state.search {
+            let line = search.render();
+            self.draw(&line, &line, &search.hint());
+            return;
+        }
+
+        let buffer = &state.buffer;
+        let prompt = self.prompt();
+        let mut line = format!(
+            "{}{}",
+            prompt,
+            helper.highlight(&buffer.text, Some(buffer.cursor))
+        );
+        if let Some(suggestion) = state.autosuggestion().filter(|_| state.menu.is_none()) {
+            line.push_str(&style(suggestion).dim().to_string());
+        } else if let Some(hint) = helper.hint(&buffer.text, buffer.cursor) {
+            line.push_str(&format!("  {}", style(hint).dim()));
+        }
+
+        let mut below = Vec::new();
+        if let Some(menu) = &state.menu {
+            let columns = (self.term.size().1 as usize).max(1);
+            let before_menu = format!("{}{}", prompt, &buffer.text[..menu.start]);
+            let (_, column) = end_position(&before_menu, columns);
+            below = menu.render(column % columns, columns);
+        } else if let Some(message) = &state.message {
+            below.push(message.clone());
+        }
+
+        let before_cursor = format!("{}{}", prompt, &buffer.text[..buffer.cursor]);
+        self.draw(&line, &before_cursor, &below);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct PlainText;
+
+    impl Helper for PlainText {
+        fn complete(&self, _line: &str, cursor: usize) -> Completion {
+            Completion {
+                start: cursor,
+                candidates: Vec::new(),
+            }
+        }
+
+        fn hint(&self, _line: &str, _cursor: usize) -> Option<String> {
+            None
+        }
+
+        fn is_complete(&self, _line: &str) -> bool {
+            true
+        }
+
+        fn highlight(&self, line: &str, _cursor: Option<usize>) -> String {
+            line.to_string()
+        }
+
+        fn check(&self, _line: &str) -> Option<(usize, String)> {
+            None
+        }
+
+        fn statement_count(&self, _line: &str) -> usize {
+            1
+        }
+    }
+
+    /// Types `keys` in vi mode into a line holding `text` with the cursor where the `|` is, and
+    /// returns the line with a `|` at the cursor. Escape is written as `<`, since `<` itself
+    /// is never needed here.
+    fn vi(text: &str, keys: &str) -> String {
+        let mut editor = LineEditor::new("");
+        editor.set_editing_mode(EditingMode::Vi);
+        let history = History::open(&std::env::temp_dir().join("oxisql-no-such-dir/history"));
+        let mut state = State::new(Trie::new());
+        state.buffer.set(&text.replacen('|', "", 1));
+        state.buffer.cursor = text.find('|').unwrap();
+
+        for c in keys.chars() {
+            let key = if c == '<' { Key::Escape } else { Key::Char(c) };
+            for command in editor.keymap.commands(&key, &state.buffer) {
+                editor.execute(command, &mut state, &PlainText, &history);
+            }
+            editor.keep_cursor_on_character(&mut state);
+        }
+
+        let mut line = state.buffer.text.clone();
+        line.insert(state.buffer.cursor, '|');
+        line
+    }
+
+    #[test]
+    fn vi_escape_enters_normal_mode_on_the_last_character() {
+        assert_eq!(vi("select 1|", "<"), "select |1");
+    }
+
+    #[test]
+    fn vi_word_motions() {
+        assert_eq!(vi("|select a.b from t", "<0w"), "select |a.b from t");
+        assert_eq!(vi("|select a.b from t", "<0ww"), "select a|.b from t");
+        assert_eq!(vi("|select a.b from t", "<0WW"), "select a.b |from t");
+        assert_eq!(vi("|select a.b from t", "<0e"), "selec|t a.b from t");
+        assert_eq!(vi("|select a.b from t", "<0E2E"), "select a.b fro|m t");
+        assert_eq!(vi("select a.b from t|", "<b"), "select a.b |from t");
+        assert_eq!(vi("select a.b from t|", "<bb"), "select a.|b from t");
+        assert_eq!(vi("select a.b from t|", "<3b"), "select a|.b from t");
+        assert_eq!(vi("select a.b from t|", "<B2B"), "|select a.b from t");
+    }
+
+    #[test]
+    fn vi_line_motions() {
+        assert_eq!(vi("  select 1|", "<0"), "|  select 1");
+        assert_eq!(vi("  select 1|", "<0^"), "  |select 1");
+        assert_eq!(vi("  select 1|", "<0$"), "  select |1");
+    }
+
+    #[test]
+    fn vi_find_motions() {
+        assert_eq!(vi("|a,b,c,d", "<0f,"), "a|,b,c,d");
+        assert_eq!(vi("|a,b,c,d", "<02f,"), "a,b|,c,d");
+        assert_eq!(vi("|a,b,c,d", "<0t,"), "|a,b,c,d");
+        assert_eq!(vi("|a,b,c,d", "<0lt,"), "a,|b,c,d");
+        assert_eq!(vi("|a,b,c,d", "<0f,;"), "a,b|,c,d");
+        assert_eq!(vi("|a,b,c,d", "<0f,;,"), "a|,b,c,d");
+        assert_eq!(vi("a,b,c,d|", "<F,"), "a,b,c|,d");
+        assert_eq!(vi("a,b,c,d|", "<T,"), "a,b,c,|d");
+        // A missing target doesn't move
+        assert_eq!(vi("|a,b,c,d", "<0fz"), "|a,b,c,d");
+    }
+
+    #[test]
+    fn vi_operators_with_counts() {
+        assert_eq!(vi("|one two three four", "<0dw"), "|two three four");
+        assert_eq!(vi("|one two three four", "<0d2w"), "|three four");
+        assert_eq!(vi("|one two three four", "<02dw"), "|three four");
+        assert_eq!(vi("|one two three four", "<02d2w"), "|");
+        assert_eq!(vi("|one two three", "<0cwuno<"), "un|o two three");
+        assert_eq!(vi("|one two three", "<0c2wx<"), "|x three");
+        assert_eq!(vi("one two three|", "<dd"), "|");
+        assert_eq!(vi("one two three|", "<ccx<"), "|x");
+        assert_eq!(vi("|one two three", "<0wD"), "one| ");
+        assert_eq!(vi("|one two three", "<0dt "), "| two three");
+        assert_eq!(vi("|one two three", "<0df "), "|two three");
+        assert_eq!(vi("|one", "<03x"), "|");
+        assert_eq!(vi("|one", "<0r_"), "|_ne");
+        assert_eq!(vi("|one", "<0~~"), "ON|e");
+    }
+
+    #[test]
+    fn vi_yank_and_paste() {
+        assert_eq!(vi("|one two", "<0ywP"), "one| one two");
+        assert_eq!(vi("|one two", "<0dwp"), "tone| wo");
+        assert_eq!(vi("|one two", "<0xp"), "n|oe two");
+    }
+
+    #[test]
+    fn vi_text_objects() {
+        assert_eq!(vi("select f(a, b)|", "<hhdi("), "select f(|)");
+        assert_eq!(vi("select f(a, b)|", "<hhda("), "select |f");
+        assert_eq!(vi("where x = 'a b'|", "<hhci'c<"), "where x = '|c'");
+        assert_eq!(vi("select one two|", "<bbdiw"), "select | two");
+        assert_eq!(vi("select one two|", "<bbdaw"), "select |two");
+    }
+
+    #[test]
+    fn vi_dot_repeats_the_last_change() {
+        assert_eq!(vi("|one two three four", "<0dw."), "|three four");
+        assert_eq!(vi("|one two three four", "<0d2w."), "|");
+        assert_eq!(vi("|a b c", "<0cwx<w."), "x |x c");
+        assert_eq!(vi("|abcd", "<0x.."), "|d");
+        // Motions in between are not repeated
+        assert_eq!(vi("|a b c d", "<0dwww."), "b c| ");
+        // Nothing to repeat yet
+        assert_eq!(vi("|abc", "<0."), "|abc");
+    }
+
+    #[test]
+    fn vi_undo() {
+        assert_eq!(vi("|one two three", "<0dwdwu"), "|two three");
+        assert_eq!(vi("|one two three", "<0dwdwuu"), "|one two three");
+        assert_eq!(vi("|one", "<0r_u"), "|one");
+    }
+}
@@ -1,6 +1,8 @@
-use std::fmt::Display;
+use console::{measure_text_width, pad_str, truncate_str, Alignment};
+use std::fmt::{Display, Write};
 
 use crate::connector::{MySqlOutputTable, MySqlResult, MySqlRowsAffected};
+use crate::describe::{Listing, TableDescription};
 
 const TOP_LEFT: &str = "┌";
 const TOP_RIGHT: &str = "┐";
@@ -15,22 +17,112 @@ const BOTTOM_T: &str = "┴";
 const LEFT_T: &str = "├";
 const RIGHT_T: &str = "┤";
 
+/// Output that is narrowed to the terminal when shown there, while the pager and the tee file
+/// get its `Display` form at full width
+pub trait Fit: Display {
+    fn fit(&self, width: usize) -> String;
+}
+
+/// Collects what `write` writes into a string
+fn fit_with(write: impl FnOnce(&mut String) -> std::fmt::Result) -> String {
+    let mut text = String::new();
+    // Writing to a String cannot fail
+    let _ = write(&mut text);
+    text
+}
+
 impl Display for MySqlOutputTable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_to(f, None)
+    }
+}
+
+impl Fit for MySqlOutputTable {
+    fn fit(&self, width: usize) -> String {
+        fit_with(|text| self.write_to(text, Some(width)))
+    }
+}
+
+impl MySqlOutputTable {
+    /// Writes the rows as a grid, narrowed to `width` columns if given
+    fn write_to(&self, f: &mut impl Write, width: Option<usize>) -> std::fmt::Result {
         if self.values.is_empty() {
             write!(f, "Empty set")?;
             return Ok(());
         }
 
-        let mut max_lengths = self.headers.iter().map(|s| s.len()).collect::<Vec<usize>>();
+        // Too many columns to fit even when cut short read better one value per line
+        let Some(widths) = fit_widths(self.column_widths(), width) else {
+            return write!(f, "{}", self.vertical());
+        };
+        self.write_grid(f, &widths)?;
+        write!(
+            f,
+            "{} row{} in set",
+            self.values.len(),
+            if self.values.len() == 1 { "" } else { "s" }
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Narrowest a column is shrunk to, enough for a few characters and the ellipsis
+const MIN_COLUMN_WIDTH: usize = 5;
+
+/// Width of the terminal output goes to, or `None` when it is not a terminal
+pub fn terminal_width() -> Option<usize> {
+    let term = console::Term::stdout();
+    term.is_term().then(|| term.size().1 as usize)
+}
+
+/// Shrinks the widest columns first until a grid of them fits in `available` columns, returning
+/// `None` when it cannot fit even with every column at its narrowest
+fn fit_widths(widths: Vec<usize>, available: Option<usize>) -> Option<Vec<usize>> {
+    let Some(available) = available else {
+        return Some(widths);
+    };
+    // Each cell is padded by a space on both sides and followed by a border
+    let borders = 3 * widths.len() + 2;
+    let total = |cap: usize| widths.iter().map(|&w| w.min(cap)).sum::<usize>() + borders;
+    if total(MIN_COLUMN_WIDTH) > available {
+        return None;
+    }
+
+    // Find the widest every column can be capped at, so only the widest ones lose anything
+    let (mut low, mut high) = (MIN_COLUMN_WIDTH, widths.iter().copied().max().unwrap_or(0));
+    while low < high {
+        let cap = (low + high).div_ceil(2);
+        if total(cap) <= available {
+            low = cap;
+        } else {
+            high = cap - 1;
+        }
+    }
+    Some(widths.iter().map(|&w| w.min(low)).collect())
+}
+
+impl MySqlOutputTable {
+    /// Width of the longest value in each column, header included
+    fn column_widths(&self) -> Vec<usize> {
+        let mut max_lengths = self
+            .headers
+            .iter()
+            .map(|s| measure_text_width(s))
+            .collect::<Vec<usize>>();
 
         for row in &self.values {
             for (i, column_name) in self.headers.iter().enumerate() {
                 let column = row.get(column_name).unwrap();
-                max_lengths[i] = max_lengths[i].max(column.len());
+                max_lengths[i] = max_lengths[i].max(measure_text_width(column));
             }
         }
+        max_lengths
+    }
 
+    /// Writes the rows as a boxed grid, one line per row, cutting values longer than their
+    /// column short
+    fn write_grid(&self, f: &mut impl Write, max_lengths: &[usize]) -> std::fmt::Result {
         // Top separator
         write!(f, "{TOP_LEFT}")?;
         for i in 0..max_lengths.len() {
@@ -46,7 +138,16 @@ impl Display for MySqlOutputTable {
         // Print headers
         write!(f, "{VERTICAL} ")?;
         for (header, max_length) in self.headers.iter().zip(max_lengths.iter()) {
-            write!(f, "{:>width$} {VERTICAL} ", header, width = max_length)?;
+            write!(
+                f,
+                "{} {VERTICAL} ",
+                pad_str(
+                    &truncate(header, *max_length),
+                    *max_length,
+                    Alignment::Right,
+                    None
+                )
+            )?;
         }
         writeln!(f)?;
 
@@ -67,7 +168,16 @@ impl Display for MySqlOutputTable {
             write!(f, "{VERTICAL} ")?;
             for (i, column_name) in self.headers.iter().enumerate() {
                 let column = row.get(column_name).unwrap();
-                write!(f, "{:>width$} {VERTICAL} ", column, width = max_lengths[i])?;
+                write!(
+                    f,
+                    "{} {VERTICAL} ",
+                    pad_str(
+                        &truncate(column, max_lengths[i]),
+                        max_lengths[i],
+                        Alignment::Right,
+                        None
+                    )
+                )?;
             }
             writeln!(f)?;
         }
@@ -82,15 +192,7 @@ impl Display for MySqlOutputTable {
                 write!(f, "{BOTTOM_T}")?;
             }
         }
-        writeln!(f, "{BOTTOM_RIGHT}")?;
-        write!(
-            f,
-            "{} row{} in set",
-            self.values.len(),
-            if self.values.len() == 1 { "" } else { "s" }
-        )?;
-
-        Ok(())
+        writeln!(f, "{BOTTOM_RIGHT}")
     }
 }
 
@@ -111,6 +213,268 @@ impl Display for MySqlResult {
         match self {
             MySqlResult::Table(table) => write!(f, "{}", table),
             MySqlResult::RowsAffected(rows_affected) => write!(f, "{}", rows_affected),
+            MySqlResult::DatabaseChanged(Some(database)) => {
+                write!(f, "Database changed to {}", database)
+            }
+            MySqlResult::DatabaseChanged(None) => write!(f, "No database selected"),
+        }
+    }
+}
+
+impl Fit for MySqlResult {
+    fn fit(&self, width: usize) -> String {
+        match self {
+            MySqlResult::Table(table) => table.fit(width),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl MySqlOutputTable {
+    /// Formats each row as a block of `column: value` lines, like the mysql client's `\G`
+    pub fn vertical(&self) -> String {
+        if self.values.is_empty() {
+            return "Empty set".to_string();
         }
+
+        let width = self
+            .headers
+            .iter()
+            .map(|h| measure_text_width(h))
+            .max()
+            .unwrap_or(0);
+        let mut output = String::new();
+        for (i, row) in self.values.iter().enumerate() {
+            output.push_str(&format!("{0} {1}. row {0}\n", "*".repeat(27), i + 1));
+            for header in &self.headers {
+                output.push_str(&format!(
+                    "{}: {}\n",
+                    pad_str(header, width, Alignment::Right, None),
+                    row.get(header).unwrap()
+                ));
+            }
+        }
+        output.push_str(&format!(
+            "{} row{} in set",
+            self.values.len(),
+            if self.values.len() == 1 { "" } else { "s" }
+        ));
+        output
+    }
+}
+
+impl MySqlResult {
+    pub fn vertical(&self) -> String {
+        match self {
+            MySqlResult::Table(table) => table.vertical(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Formats a byte count with a binary unit, like `12.5 MiB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+impl Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.title)?;
+        write!(f, "{}", self.table)
+    }
+}
+
+impl Fit for Listing {
+    fn fit(&self, width: usize) -> String {
+        format!("{}\n{}", self.title, self.table.fit(width))
+    }
+}
+
+impl Display for TableDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_to(f, None)
+    }
+}
+
+impl Fit for TableDescription {
+    fn fit(&self, width: usize) -> String {
+        fit_with(|text| self.write_to(text, Some(width)))
+    }
+}
+
+impl TableDescription {
+    /// Writes the description, narrowing its columns grid to `width` columns if given
+    fn write_to(&self, f: &mut impl Write, width: Option<usize>) -> std::fmt::Result {
+        let kind = if self.is_view { "View" } else { "Table" };
+        write!(f, "{} \"{}.{}\"", kind, self.database, self.name)?;
+        if !self.engine.is_empty() {
+            write!(f, " ({})", self.engine)?;
+        }
+        writeln!(f)?;
+        if !self.comment.is_empty() {
+            writeln!(f, "Comment: {}", self.comment)?;
+        }
+        let widths = self.columns.column_widths();
+        let fitted = fit_widths(widths.clone(), width);
+        self.columns
+            .write_grid(f, fitted.as_ref().unwrap_or(&widths))?;
+
+        for (title, lines) in [
+            ("Indexes:", &self.indexes),
+            ("Foreign-key constraints:", &self.foreign_keys),
+            ("Referenced by:", &self.referenced_by),
+        ] {
+            if !lines.is_empty() {
+                writeln!(f, "{}", title)?;
+                for line in lines {
+                    writeln!(f, "    {}", line)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats seconds like the mysql client's uptime, as in `3 days 4 hours 5 min 6 sec`
+pub fn format_duration(seconds: u64) -> String {
+    let parts = [
+        (seconds / 86400, "day"),
+        (seconds / 3600 % 24, "hour"),
+        (seconds / 60 % 60, "min"),
+        (seconds % 60, "sec"),
+    ];
+    let mut output = Vec::new();
+    for (count, unit) in parts {
+        if count == 0 && output.is_empty() && unit != "sec" {
+            continue;
+        }
+        let plural = if count != 1 && matches!(unit, "day" | "hour") {
+            "s"
+        } else {
+            ""
+        };
+        output.push(format!("{} {}{}", count, unit, plural));
+    }
+    output.join(" ")
+}
+
+/// Lines up `label: value` pairs between rules, like the mysql client's `status`
+pub fn status(entries: &[(&str, String)]) -> String {
+    let width = entries
+        .iter()
+        .map(|(label, _)| measure_text_width(label) + 1)
+        .max()
+        .unwrap_or(0);
+    let mut output = String::from("--------------\n");
+    for (label, value) in entries {
+        output.push_str(&format!(
+            "{} {}\n",
+            pad_str(&format!("{label}:"), width, Alignment::Left, None),
+            value
+        ));
+    }
+    output.push_str("--------------");
+    output
+}
+
+/// Cuts `text` down to `width` columns, ending it with an ellipsis when anything was cut
+pub fn truncate(text: &str, width: usize) -> String {
+    if measure_text_width(text) <= width {
+        text.to_string()
+    } else {
+        // console counts the tail against the width even when the text fits, so check first
+        truncate_str(text, width, "…").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn table(headers: &[&str], rows: &[&[&str]]) -> MySqlOutputTable {
+        MySqlOutputTable {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            values: rows
+                .iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .zip(row.iter())
+                        .map(|(h, v)| (h.to_string(), v.to_string()))
+                        .collect::<HashMap<_, _>>()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn fit_widths_keeps_columns_that_already_fit() {
+        assert_eq!(fit_widths(vec![2, 3, 4], None), Some(vec![2, 3, 4]));
+        assert_eq!(fit_widths(vec![2, 3, 4], Some(100)), Some(vec![2, 3, 4]));
+        // Exactly as wide as the terminal: 9 of values and 11 of borders
+        assert_eq!(fit_widths(vec![2, 3, 4], Some(20)), Some(vec![2, 3, 4]));
+    }
+
+    #[test]
+    fn fit_widths_caps_only_the_wide_column() {
+        assert_eq!(fit_widths(vec![3, 4, 40], Some(30)), Some(vec![3, 4, 12]));
+        // Narrow columns are not widened to the minimum
+        assert_eq!(fit_widths(vec![2, 2, 20], Some(25)), Some(vec![2, 2, 10]));
+        assert_eq!(fit_widths(vec![30, 40], Some(28)), Some(vec![10, 10]));
+    }
+
+    #[test]
+    fn fit_widths_gives_up_below_the_minimum_column_width() {
+        assert_eq!(fit_widths(vec![10; 10], Some(60)), None);
+        assert_eq!(
+            fit_widths(vec![20, 20, 20], Some(MIN_COLUMN_WIDTH * 3)),
+            None
+        );
+        assert_eq!(fit_widths(vec![2, 2, 20], Some(19)), None);
+        assert_eq!(fit_widths(vec![2, 2, 20], Some(20)), Some(vec![2, 2, 5]));
+    }
+
+    #[test]
+    fn fit_leaves_a_narrow_table_alone() {
+        let table = table(&["id", "name"], &[&["1", "alice"], &["2", "bob"]]);
+        assert_eq!(table.fit(80), table.to_string());
+        assert!(table.to_string().ends_with("2 rows in set"));
+    }
+
+    #[test]
+    fn fit_cuts_the_wide_column_short() {
+        let description = "x".repeat(100);
+        let table = table(&["id", "description"], &[&["1", &description]]);
+        let fitted = table.fit(40);
+        assert!(fitted.lines().all(|line| measure_text_width(line) <= 40));
+        assert!(fitted.contains("│  1 │"));
+        assert!(fitted.contains("x…"));
+        assert!(fitted.ends_with("1 row in set"));
+        // The full width is kept for the pager and the tee file
+        assert!(table.to_string().contains(&description));
+    }
+
+    #[test]
+    fn fit_shows_too_many_columns_vertically() {
+        let headers = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let values = ["value"; 8];
+        let table = table(&headers, &[&values]);
+        assert_eq!(table.fit(40), table.vertical());
+        assert_eq!(
+            MySqlResult::Table(table).fit(40).lines().nth(1),
+            Some("a: value")
+        );
     }
 }
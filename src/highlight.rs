@@ -0,0 +1,173 @@
+use console::Style;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::keywords::{function, is_keyword};
+use crate::lexer::{matching_delimiter, tokenize, TokenKind};
+
+/// Colors of each kind of token, written as console styles like `cyan.bold`; an empty
+/// string leaves the token uncolored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub keyword: String,
+    pub function: String,
+    pub string: String,
+    pub number: String,
+    pub comment: String,
+    pub identifier: String,
+    pub unknown_identifier: String,
+    /// The bracket or quote under the cursor and its partner
+    pub matching_delimiter: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            keyword: "blue.bold".to_string(),
+            function: "cyan".to_string(),
+            string: "green".to_string(),
+            number: "magenta".to_string(),
+            comment: "dim".to_string(),
+            identifier: "yellow".to_string(),
+            unknown_identifier: String::new(),
+            matching_delimiter: "bold.underlined".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn default_path() -> PathBuf {
+        let home = &std::env::var("HOME").unwrap();
+        Path::new(home).join(".config/oxisql/theme.json")
+    }
+
+    /// Reads the theme from `filename`, keeping the default colors for anything it leaves out
+    pub fn load(filename: &Path) -> Theme {
+        let Ok(file) = File::open(filename) else {
+            return Theme::default();
+        };
+        match serde_json::from_reader(file) {
+            Ok(theme) => theme,
+            Err(e) => {
+                eprintln!("[-] Could not read theme {}: {}", filename.display(), e);
+                Theme::default()
+            }
+        }
+    }
+}
+
+fn push_styled(highlighted: &mut String, text: &str, style: Option<&Style>) {
+    match style {
+        Some(style) if !text.is_empty() => highlighted.push_str(&style.apply_to(text).to_string()),
+        _ => highlighted.push_str(text),
+    }
+}
+
+/// Colors SQL statements with a theme
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    keyword: Style,
+    function: Style,
+    string: Style,
+    number: Style,
+    comment: Style,
+    identifier: Style,
+    unknown_identifier: Style,
+    matching_delimiter: Style,
+}
+
+impl Highlighter {
+    pub fn new(theme: &Theme) -> Self {
+        Self {
+            keyword: Style::from_dotted_str(&theme.keyword),
+            function: Style::from_dotted_str(&theme.function),
+            string: Style::from_dotted_str(&theme.string),
+            number: Style::from_dotted_str(&theme.number),
+            comment: Style::from_dotted_str(&theme.comment),
+            identifier: Style::from_dotted_str(&theme.identifier),
+            unknown_identifier: Style::from_dotted_str(&theme.unknown_identifier),
+            matching_delimiter: Style::from_dotted_str(&theme.matching_delimiter),
+        }
+    }
+
+    /// Returns `input` with every token styled, where `is_symbol` tells identifiers known from
+    /// the schema apart from unknown ones. A bracket or quote at or just before `cursor` is
+    /// marked along with its partner.
+    pub fn highlight(
+        &self,
+        input: &str,
+        cursor: Option<usize>,
+        is_symbol: impl Fn(&str) -> bool,
+    ) -> String {
+        let tokens = tokenize(input);
+        let mut highlighted = String::with_capacity(input.len());
+
+        let mut marked: Vec<usize> = Vec::new();
+        if let Some(cursor) = cursor {
+            let candidates = [Some(cursor), cursor.checked_sub(1)];
+            if let Some((position, partner)) = candidates
+                .into_iter()
+                .flatten()
+                .filter(|&position| input.is_char_boundary(position))
+                .find_map(|position| Some((position, matching_delimiter(input, position)?)))
+            {
+                marked = vec![position, partner];
+            }
+        }
+
+        for (i, token) in tokens.iter().enumerate() {
+            let text = token.text(input);
+            let style = match token.kind {
+                TokenKind::Whitespace | TokenKind::Punctuation => None,
+                TokenKind::String => Some(&self.string),
+                TokenKind::Number => Some(&self.number),
+                TokenKind::Comment => Some(&self.comment),
+                TokenKind::QuotedIdentifier => {
+                    if is_symbol(text.trim_matches('`')) {
+                        Some(&self.identifier)
+                    } else {
+                        Some(&self.unknown_identifier)
+                    }
+                }
+                TokenKind::Word => {
+                    let called = tokens[i + 1..]
+                        .iter()
+                        .find(|t| t.kind != TokenKind::Whitespace)
+                        .is_some_and(|t| t.text(input) == "(");
+                    if called && function(text).is_some() {
+                        Some(&self.function)
+                    } else if is_symbol(text) {
+                        Some(&self.identifier)
+                    } else if is_keyword(text) {
+                        Some(&self.keyword)
+                    } else {
+                        Some(&self.unknown_identifier)
+                    }
+                }
+            };
+
+            // Delimiters are single ASCII characters, marked on their own
+            let mut start = token.start;
+            let mut positions: Vec<usize> = marked
+                .iter()
+                .copied()
+                .filter(|&p| token.start <= p && p < token.end)
+                .collect();
+            positions.sort();
+            for position in positions {
+                push_styled(&mut highlighted, &input[start..position], style);
+                push_styled(
+                    &mut highlighted,
+                    &input[position..position + 1],
+                    Some(&self.matching_delimiter),
+                );
+                start = position + 1;
+            }
+            push_styled(&mut highlighted, &input[start..token.end], style);
+        }
+
+        highlighted
+    }
+}
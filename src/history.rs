@@ -0,0 +1,694 @@
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::trie::{fuzzy_score, Trie};
+
+/// A statement run in some session. Everything but the statement is optional because
+/// entries migrated from older history files don't carry it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub statement: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+    /// Marks a request to forget every earlier run of this statement
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(statement: &str) -> Self {
+        Self {
+            statement: statement.to_string(),
+            timestamp: None,
+            host: None,
+            database: None,
+            duration_ms: None,
+            success: None,
+            deleted: false,
+        }
+    }
+
+    /// How much a single run counts towards frecency, decaying with age
+    fn weight(&self, now: DateTime<Utc>) -> f64 {
+        let Some(timestamp) = self.timestamp else {
+            return 0.25;
+        };
+        let age = now - timestamp;
+        if age < Duration::hours(1) {
+            4.0
+        } else if age < Duration::days(1) {
+            2.0
+        } else if age < Duration::weeks(1) {
+            1.0
+        } else if age < Duration::days(30) {
+            0.5
+        } else {
+            0.25
+        }
+    }
+}
+
+type HistoryParser = fn(&str) -> Vec<HistoryEntry>;
+
+/// Parses `~/.mysql_history`, one statement per line. The libedit build of the client starts
+/// the file with `_HiStOrY_V2_` and escapes lines, while readline builds and MariaDB store them
+/// as typed.
+pub fn parse_mysql_history(contents: &str) -> Vec<HistoryEntry> {
+    let mut lines = contents.lines().peekable();
+    let escaped = lines.next_if_eq(&"_HiStOrY_V2_").is_some();
+    lines
+        .map(|line| {
+            if escaped {
+                unescape_octal(line)
+            } else {
+                line.to_string()
+            }
+        })
+        .filter(|statement| !statement.trim().is_empty())
+        .map(|statement| HistoryEntry::new(statement.trim()))
+        .collect()
+}
+
+/// Decodes libedit's escapes: `\ooo` for single bytes of the UTF-8 encoded line, like `\040`
+/// for a space, and `\\` for a backslash
+fn unescape_octal(line: &str) -> String {
+    let mut unescaped = Vec::new();
+    let mut rest = line;
+    while let Some(i) = rest.find('\\') {
+        unescaped.extend_from_slice(&rest.as_bytes()[..i]);
+        rest = &rest[i + 1..];
+
+        let escaped = rest
+            .get(..3)
+            .filter(|digits| digits.chars().all(|c| c.is_digit(8)))
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match escaped {
+            Some(byte) => {
+                unescaped.push(byte);
+                rest = &rest[3..];
+            }
+            None => {
+                unescaped.push(b'\\');
+                rest = rest.strip_prefix('\\').unwrap_or(rest);
+            }
+        }
+    }
+    unescaped.extend_from_slice(rest.as_bytes());
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// Parses mycli's history file, where each entry is a `# <timestamp>` line followed by the
+/// statement's lines, each prefixed with `+`.
+pub fn parse_mycli_history(contents: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut timestamp = None;
+    let mut lines: Vec<&str> = Vec::new();
+
+    for line in contents.lines().chain(std::iter::once("")) {
+        if let Some(line) = line.strip_prefix('+') {
+            lines.push(line);
+            continue;
+        }
+
+        if !lines.is_empty() {
+            let statement = lines.join("\n");
+            if !statement.trim().is_empty() {
+                entries.push(HistoryEntry {
+                    timestamp,
+                    ..HistoryEntry::new(statement.trim())
+                });
+            }
+            lines.clear();
+        }
+
+        if let Some(time) = line.strip_prefix("# ") {
+            timestamp = NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .and_then(|time| time.and_local_timezone(Local).single())
+                .map(|time| time.with_timezone(&Utc));
+        }
+    }
+
+    entries
+}
+
+/// Collapses whitespace so statements differing only in formatting count as one
+pub fn normalize(statement: &str) -> String {
+    statement
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Append-only history file with one JSON entry per line. Every statement is appended as
+/// soon as it has run, under an exclusive lock so concurrent sessions interleave whole lines.
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn default_path() -> PathBuf {
+        let home = &std::env::var("HOME").unwrap();
+        Path::new(home).join(".cache/oxisql/history.jsonl")
+    }
+
+    pub fn open(path: &Path) -> Self {
+        let mut history = Self {
+            path: path.to_path_buf(),
+            entries: Vec::new(),
+        };
+
+        if let Ok(file) = File::open(path) {
+            // Skip lines that don't parse, e.g. one cut short by a crash, possibly in the middle
+            // of a character
+            let entries = BufReader::new(file)
+                .split(b'\n')
+                .map_while(Result::ok)
+                .filter_map(|line| {
+                    serde_json::from_str::<HistoryEntry>(&String::from_utf8_lossy(&line)).ok()
+                });
+            for entry in entries {
+                if entry.deleted {
+                    history.forget(&entry.statement);
+                } else {
+                    history.entries.push(entry);
+                }
+            }
+        } else {
+            history.migrate_trie_file();
+        }
+
+        history
+    }
+
+    /// Carries over statements from the JSON trie file used by earlier versions
+    fn migrate_trie_file(&mut self) {
+        let trie_file_path = self.path.with_file_name("queries.trie.json");
+        let Some(trie) = Trie::from_file(&trie_file_path) else {
+            return;
+        };
+
+        let entries: Vec<HistoryEntry> = trie
+            .search_ignore_case("")
+            .iter()
+            .map(|statement| HistoryEntry::new(statement))
+            .collect();
+        if let Err(e) = self.append_all(&entries) {
+            eprintln!("[-] Could not migrate history: {}", e);
+        }
+    }
+
+    pub fn append(&mut self, entry: HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.append_all(std::slice::from_ref(&entry))
+    }
+
+    pub fn append_all(
+        &mut self,
+        entries: &[HistoryEntry],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(self.path.parent().unwrap())?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        file.lock()?;
+        // A crash mid-write can leave the last line unterminated, so start on a fresh line
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                lines.insert(0, '\n');
+            }
+        }
+        file.write_all(lines.as_bytes())?;
+        file.unlock()?;
+
+        self.entries.extend_from_slice(entries);
+        Ok(())
+    }
+
+    /// Appends the statements not already in history and returns how many were new
+    pub fn import(
+        &mut self,
+        entries: Vec<HistoryEntry>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut seen: HashSet<String> = self
+            .entries
+            .iter()
+            .map(|entry| normalize(&entry.statement))
+            .collect();
+        let entries: Vec<HistoryEntry> = entries
+            .into_iter()
+            .filter(|entry| seen.insert(normalize(&entry.statement)))
+            .collect();
+        self.append_all(&entries)?;
+        Ok(entries.len())
+    }
+
+    /// Imports the history files of the mysql client and mycli from their default locations
+    pub fn import_from_clients(&mut self) {
+        let home = &std::env::var("HOME").unwrap();
+        let mysql_history = std::env::var("MYSQL_HISTFILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| Path::new(home).join(".mysql_history"));
+        let sources: [(PathBuf, HistoryParser); 3] = [
+            (mysql_history, parse_mysql_history),
+            (Path::new(home).join(".mycli-history"), parse_mycli_history),
+            (
+                Path::new(home).join(".config/mycli/history"),
+                parse_mycli_history,
+            ),
+        ];
+
+        let mut found = false;
+        for (path, parse) in sources {
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            found = true;
+            match self.import(parse(&contents)) {
+                Ok(imported) => {
+                    println!(
+                        "[+] Imported {} statements from {}",
+                        imported,
+                        path.display()
+                    )
+                }
+                Err(e) => eprintln!("[-] Could not import {}: {}", path.display(), e),
+            }
+        }
+
+        if !found {
+            eprintln!("[-] No mysql or mycli history files found");
+        }
+    }
+
+    /// Removes every run of `statement` from history
+    pub fn delete(&mut self, statement: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let tombstone = HistoryEntry {
+            timestamp: Some(Utc::now()),
+            deleted: true,
+            ..HistoryEntry::new(statement)
+        };
+        self.append(tombstone)?;
+        self.forget(statement);
+        Ok(())
+    }
+
+    fn forget(&mut self, statement: &str) {
+        let statement = normalize(statement);
+        self.entries
+            .retain(|entry| normalize(&entry.statement) != statement);
+    }
+
+    /// Unique statements ranked by frecency, lowest first. Each run adds a weight that decays
+    /// with its age, and the latest formatting of a statement is the one kept.
+    pub fn ranked(&self) -> Vec<String> {
+        self.ranked_at(Utc::now())
+    }
+
+    fn ranked_at(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut scores: HashMap<String, (f64, usize, &str)> = HashMap::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let score = scores
+                .entry(normalize(&entry.statement))
+                .or_insert((0.0, i, ""));
+            score.0 += entry.weight(now);
+            score.1 = i;
+            score.2 = &entry.statement;
+        }
+
+        let mut ranked: Vec<(f64, usize, &str)> = scores.into_values().collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        ranked
+            .into_iter()
+            .map(|(_, _, statement)| statement.to_string())
+            .collect()
+    }
+
+    /// Prefix search index over unique statements, inserted in frecency order
+    pub fn trie(&self) -> Trie {
+        let mut trie = Trie::new();
+        for statement in self.ranked() {
+            trie.insert(&statement);
+        }
+        trie
+    }
+}
+
+/// Readline-style reverse incremental search over past statements
+#[derive(Debug)]
+pub struct HistorySearch {
+    pub query: String,
+    /// Past statements, lowest ranked first
+    history: Vec<String>,
+    /// Indexes into `history`, best match first
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl HistorySearch {
+    pub fn new(history: Vec<String>) -> Self {
+        let mut search = Self {
+            query: String::new(),
+            history,
+            matches: Vec::new(),
+            selected: 0,
+        };
+        search.update();
+        search
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    pub fn pop(&mut self) {
+        if let Some((i, _)) = self.query.grapheme_indices(true).next_back() {
+            self.query.truncate(i);
+        }
+        self.update();
+    }
+
+    /// Drops the current match from the search and returns it
+    pub fn remove_current(&mut self) -> Option<String> {
+        let index = *self.matches.get(self.selected)?;
+        let removed = self.history.remove(index);
+        let selected = self.selected;
+        self.update();
+        self.selected = selected.min(self.matches.len().saturating_sub(1));
+        Some(removed)
+    }
+
+    /// Moves on to the next match, staying on the last one when there are no more
+    pub fn older(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|i| self.history[*i].as_str())
+    }
+
+    /// Substring matches come first, best ranked first, followed by fuzzy matches by score
+    fn update(&mut self) {
+        let query = self.query.to_lowercase();
+        let mut substring = Vec::new();
+        let mut fuzzy = Vec::new();
+        for (i, entry) in self.history.iter().enumerate().rev() {
+            if entry.to_lowercase().contains(&query) {
+                substring.push(i);
+            } else if let Some(score) = fuzzy_score(&query, entry) {
+                fuzzy.push((score, i));
+            }
+        }
+        fuzzy.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        self.matches = substring;
+        self.matches.extend(fuzzy.into_iter().map(|(_, i)| i));
+        self.selected = 0;
+    }
+
+    /// The search prompt followed by the current match with the query highlighted
+    pub fn render(&self) -> String {
+        let Some(found) = self.current() else {
+            return format!("(failing reverse-i-search)`{}': ", self.query);
+        };
+
+        let range = found
+            .to_lowercase()
+            .find(&self.query.to_lowercase())
+            .map(|start| (start, start + self.query.len()))
+            .filter(|(start, end)| {
+                // Lowercasing can change byte lengths outside ASCII
+                !self.query.is_empty()
+                    && found.is_char_boundary(*start)
+                    && found.is_char_boundary(*end)
+            });
+        let found = match range {
+            Some((start, end)) => {
+                format!(
+                    "{}{}{}",
+                    &found[..start],
+                    style(&found[start..end]).underlined(),
+                    &found[end..]
+                )
+            }
+            _ => found.to_string(),
+        };
+        format!("(reverse-i-search)`{}': {}", self.query, found)
+    }
+
+    /// Keys available on a match, shown under the search prompt
+    pub fn hint(&self) -> Vec<String> {
+        if self.current().is_none() {
+            return Vec::new();
+        }
+        vec![style("Ctrl-R older match, Delete forget this entry")
+            .dim()
+            .to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history file of its own in the temporary directory, removed when dropped
+    struct TempHistory(PathBuf);
+
+    impl TempHistory {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "oxisql-test-{}-{}.jsonl",
+                std::process::id(),
+                name
+            ));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempHistory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn statements(history: &History) -> Vec<&str> {
+        history
+            .entries
+            .iter()
+            .map(|entry| entry.statement.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn open_skips_corrupt_lines_and_keeps_reading() {
+        let mut contents = b"{\"statement\":\"select 1;\"}\n".to_vec();
+        // Cut short in the middle of the two bytes of an é
+        contents.extend_from_slice(b"{\"statement\":\"select 'caf\xc3");
+        contents.extend_from_slice(b"\n{\"statement\":\"select 2;\"}\nnot json\n");
+        contents.extend_from_slice(b"{\"statement\":\"select 3;\"}\n");
+        let file = TempHistory::new("corrupt", &contents);
+
+        let history = History::open(&file.0);
+        assert_eq!(
+            statements(&history),
+            ["select 1;", "select 2;", "select 3;"]
+        );
+    }
+
+    #[test]
+    fn open_applies_tombstones() {
+        let file = TempHistory::new(
+            "tombstones",
+            b"{\"statement\":\"select 1;\"}\n{\"statement\":\"select  1;\",\"deleted\":true}\n\
+              {\"statement\":\"select 2;\"}\n",
+        );
+        let history = History::open(&file.0);
+        assert_eq!(statements(&history), ["select 2;"]);
+    }
+
+    fn entry_at(statement: &str, now: DateTime<Utc>, age: Duration) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: Some(now - age),
+            ..HistoryEntry::new(statement)
+        }
+    }
+
+    #[test]
+    fn ranked_prefers_recent_and_frequent_statements() {
+        let now = Utc::now();
+        let history = History {
+            path: PathBuf::new(),
+            entries: vec![
+                entry_at("select old", now, Duration::days(60)),
+                entry_at("select old", now, Duration::days(60)),
+                entry_at("select old", now, Duration::days(60)),
+                entry_at("select recent", now, Duration::minutes(5)),
+                entry_at("select recent", now, Duration::minutes(5)),
+                entry_at("select recent", now, Duration::minutes(5)),
+                entry_at("select once", now, Duration::minutes(5)),
+            ],
+        };
+        assert_eq!(
+            history.ranked_at(now),
+            ["select old", "select once", "select recent"]
+        );
+    }
+
+    #[test]
+    fn ranked_breaks_ties_by_last_use_and_keeps_the_latest_formatting() {
+        let now = Utc::now();
+        let history = History {
+            path: PathBuf::new(),
+            entries: vec![
+                entry_at("select  1", now, Duration::days(2)),
+                entry_at("select 2", now, Duration::days(2)),
+                entry_at("select 1", now, Duration::days(2)),
+                entry_at("select 3", now, Duration::days(3)),
+                entry_at("select 3", now, Duration::days(3)),
+            ],
+        };
+        assert_eq!(history.ranked_at(now), ["select 2", "select 1", "select 3"]);
+    }
+
+    #[test]
+    fn ranked_leaves_out_deleted_statements() {
+        let file = TempHistory::new(
+            "ranked",
+            b"{\"statement\":\"select 1;\"}\n{\"statement\":\"select 2;\"}\n\
+              {\"statement\":\"select 1;\",\"deleted\":true}\n",
+        );
+        let history = History::open(&file.0);
+        assert_eq!(history.ranked(), ["select 2;"]);
+    }
+
+    fn search(history: &[&str], query: &str) -> HistorySearch {
+        let mut search = HistorySearch::new(history.iter().map(|s| s.to_string()).collect());
+        for c in query.chars() {
+            search.push(c);
+        }
+        search
+    }
+
+    #[test]
+    fn search_cycles_from_best_ranked_to_older_matches() {
+        let mut search = search(
+            &["select a from t", "show tables", "select b from t"],
+            "sel",
+        );
+        assert_eq!(search.current(), Some("select b from t"));
+        search.older();
+        assert_eq!(search.current(), Some("select a from t"));
+        // Stays on the last match
+        search.older();
+        assert_eq!(search.current(), Some("select a from t"));
+    }
+
+    #[test]
+    fn search_puts_substring_matches_before_fuzzy_ones() {
+        let search = search(&["SHOW TABLES", "select * from status"], "stat");
+        assert_eq!(search.current(), Some("select * from status"));
+        let search = self::search(&["show tables", "select 1"], "shtb");
+        assert_eq!(search.current(), Some("show tables"));
+    }
+
+    #[test]
+    fn search_restarts_when_the_query_changes() {
+        let mut search = search(&["select 1", "select 12", "show tables"], "select 1");
+        search.older();
+        assert_eq!(search.current(), Some("select 1"));
+        search.push('2');
+        assert_eq!(search.current(), Some("select 12"));
+        search.pop();
+        assert_eq!(search.current(), Some("select 12"));
+        search.push('x');
+        assert_eq!(search.current(), None);
+        assert!(search.render().starts_with("(failing reverse-i-search)"));
+    }
+
+    #[test]
+    fn search_remove_current_moves_to_the_next_match() {
+        let mut search = search(&["select 1", "select 2", "select 3"], "select");
+        assert_eq!(search.remove_current().as_deref(), Some("select 3"));
+        assert_eq!(search.current(), Some("select 2"));
+        search.older();
+        assert_eq!(search.remove_current().as_deref(), Some("select 1"));
+        assert_eq!(search.current(), Some("select 2"));
+    }
+
+    #[test]
+    fn unescape_octal_decodes_escaped_bytes_as_utf8() {
+        assert_eq!(unescape_octal(r"select\040'caf\303\251'"), "select 'café'");
+    }
+
+    #[test]
+    fn unescape_octal_decodes_escaped_backslashes_only() {
+        assert_eq!(unescape_octal(r"select 'a\nb'"), r"select 'a\nb'");
+        assert_eq!(unescape_octal(r"select '\\'"), r"select '\'");
+    }
+
+    #[test]
+    fn parse_mysql_history_keeps_backslashes_without_the_libedit_header() {
+        let entries = parse_mysql_history("select '\\\\n';\nselect\\0401;\n");
+        let statements: Vec<_> = entries.iter().map(|e| e.statement.as_str()).collect();
+        assert_eq!(statements, [r"select '\\n';", r"select\0401;"]);
+    }
+
+    #[test]
+    fn parse_mycli_history_joins_lines_and_reads_timestamps() {
+        let entries = parse_mycli_history(
+            "\n# 2024-03-01 10:20:30.123456\n+select *\n+from t;\n\n# not a time\n+show tables;\n",
+        );
+        let statements: Vec<_> = entries.iter().map(|e| e.statement.as_str()).collect();
+        assert_eq!(statements, ["select *\nfrom t;", "show tables;"]);
+
+        let expected = NaiveDateTime::parse_from_str("2024-03-01 10:20:30", "%Y-%m-%d %H:%M:%S")
+            .unwrap()
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            entries[0].timestamp.map(|t| t.timestamp()),
+            Some(expected.timestamp())
+        );
+        assert_eq!(entries[1].timestamp, None);
+    }
+
+    #[test]
+    fn parse_mysql_history_skips_header_and_blank_lines() {
+        let entries = parse_mysql_history("_HiStOrY_V2_\nselect\\0401;\n\\040\nshow\\040tables;\n");
+        let statements: Vec<_> = entries.iter().map(|e| e.statement.as_str()).collect();
+        assert_eq!(statements, ["select 1;", "show tables;"]);
+    }
+}
@@ -0,0 +1,408 @@
+/// MySQL keywords offered by completion, most commonly typed first
+pub const KEYWORDS: &[&str] = &[
+    "SELECT",
+    "FROM",
+    "WHERE",
+    "AND",
+    "OR",
+    "NOT",
+    "NULL",
+    "IS",
+    "IN",
+    "AS",
+    "ON",
+    "JOIN",
+    "LEFT",
+    "RIGHT",
+    "INNER",
+    "OUTER",
+    "CROSS",
+    "NATURAL",
+    "STRAIGHT_JOIN",
+    "USING",
+    "GROUP",
+    "ORDER",
+    "BY",
+    "HAVING",
+    "LIMIT",
+    "OFFSET",
+    "ASC",
+    "DESC",
+    "DISTINCT",
+    "DISTINCTROW",
+    "ALL",
+    "ANY",
+    "SOME",
+    "EXISTS",
+    "BETWEEN",
+    "LIKE",
+    "REGEXP",
+    "RLIKE",
+    "ESCAPE",
+    "CASE",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "END",
+    "UNION",
+    "INTERSECT",
+    "EXCEPT",
+    "WITH",
+    "RECURSIVE",
+    "INSERT",
+    "INTO",
+    "VALUES",
+    "VALUE",
+    "UPDATE",
+    "SET",
+    "DELETE",
+    "REPLACE",
+    "IGNORE",
+    "DUPLICATE",
+    "KEY",
+    "USE",
+    "FORCE",
+    "INDEX",
+    "SHOW",
+    "DESCRIBE",
+    "EXPLAIN",
+    "ANALYZE",
+    "FORMAT",
+    "CREATE",
+    "ALTER",
+    "DROP",
+    "RENAME",
+    "TRUNCATE",
+    "TABLE",
+    "TABLES",
+    "VIEW",
+    "DATABASE",
+    "DATABASES",
+    "SCHEMA",
+    "SCHEMAS",
+    "COLUMN",
+    "COLUMNS",
+    "PRIMARY",
+    "FOREIGN",
+    "REFERENCES",
+    "UNIQUE",
+    "CONSTRAINT",
+    "CHECK",
+    "DEFAULT",
+    "AUTO_INCREMENT",
+    "COMMENT",
+    "ENGINE",
+    "CHARSET",
+    "CHARACTER",
+    "COLLATE",
+    "TEMPORARY",
+    "IF",
+    "CASCADE",
+    "RESTRICT",
+    "ADD",
+    "MODIFY",
+    "CHANGE",
+    "AFTER",
+    "FIRST",
+    "PROCEDURE",
+    "FUNCTION",
+    "TRIGGER",
+    "EVENT",
+    "RETURNS",
+    "BEGIN",
+    "COMMIT",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "RELEASE",
+    "START",
+    "TRANSACTION",
+    "LOCK",
+    "UNLOCK",
+    "SHARE",
+    "MODE",
+    "FOR",
+    "NOWAIT",
+    "SKIP",
+    "LOCKED",
+    "CALL",
+    "DECLARE",
+    "GRANT",
+    "REVOKE",
+    "PRIVILEGES",
+    "USER",
+    "IDENTIFIED",
+    "TO",
+    "STATUS",
+    "VARIABLES",
+    "PROCESSLIST",
+    "GLOBAL",
+    "SESSION",
+    "FULL",
+    "KILL",
+    "QUERY",
+    "CONNECTION",
+    "PARTITION",
+    "PARTITIONS",
+    "OVER",
+    "WINDOW",
+    "ROWS",
+    "RANGE",
+    "PRECEDING",
+    "FOLLOWING",
+    "UNBOUNDED",
+    "CURRENT",
+    "ROW",
+    "INTERVAL",
+    "BINARY",
+    "TRUE",
+    "FALSE",
+    "UNKNOWN",
+    "DIV",
+    "MOD",
+    "XOR",
+    "SQL_CALC_FOUND_ROWS",
+    "HIGH_PRIORITY",
+    "LOW_PRIORITY",
+    "DELAYED",
+    "QUICK",
+    "OPTIMIZE",
+    "REPAIR",
+    "FLUSH",
+    "RESET",
+    "PURGE",
+    "LOAD",
+    "DATA",
+    "INFILE",
+    "OUTFILE",
+    "FIELDS",
+    "TERMINATED",
+    "ENCLOSED",
+    "LINES",
+    "TINYINT",
+    "SMALLINT",
+    "MEDIUMINT",
+    "INT",
+    "INTEGER",
+    "BIGINT",
+    "DECIMAL",
+    "NUMERIC",
+    "FLOAT",
+    "DOUBLE",
+    "BIT",
+    "BOOLEAN",
+    "CHAR",
+    "VARCHAR",
+    "TINYTEXT",
+    "TEXT",
+    "MEDIUMTEXT",
+    "LONGTEXT",
+    "VARBINARY",
+    "BLOB",
+    "MEDIUMBLOB",
+    "LONGBLOB",
+    "ENUM",
+    "JSON",
+    "DATE",
+    "TIME",
+    "DATETIME",
+    "TIMESTAMP",
+    "YEAR",
+    "UNSIGNED",
+    "ZEROFILL",
+    "SIGNED",
+];
+
+/// A built-in function and its argument signature
+#[derive(Debug)]
+pub struct Function {
+    pub name: &'static str,
+    pub signature: &'static str,
+}
+
+macro_rules! functions {
+    ($($name:literal => $signature:literal),* $(,)?) => {
+        &[$(Function { name: $name, signature: $signature }),*]
+    };
+}
+
+/// MySQL built-in functions
+pub const FUNCTIONS: &[Function] = functions![
+    // Aggregates
+    "COUNT" => "COUNT([DISTINCT] expr)",
+    "SUM" => "SUM([DISTINCT] expr)",
+    "AVG" => "AVG([DISTINCT] expr)",
+    "MIN" => "MIN([DISTINCT] expr)",
+    "MAX" => "MAX([DISTINCT] expr)",
+    "GROUP_CONCAT" => "GROUP_CONCAT([DISTINCT] expr [ORDER BY ...] [SEPARATOR str])",
+    "JSON_ARRAYAGG" => "JSON_ARRAYAGG(col_or_expr)",
+    "JSON_OBJECTAGG" => "JSON_OBJECTAGG(key, value)",
+    "STDDEV" => "STDDEV(expr)",
+    "STDDEV_POP" => "STDDEV_POP(expr)",
+    "STDDEV_SAMP" => "STDDEV_SAMP(expr)",
+    "VARIANCE" => "VARIANCE(expr)",
+    "VAR_POP" => "VAR_POP(expr)",
+    "VAR_SAMP" => "VAR_SAMP(expr)",
+    "BIT_AND" => "BIT_AND(expr)",
+    "BIT_OR" => "BIT_OR(expr)",
+    "BIT_XOR" => "BIT_XOR(expr)",
+    // Window functions
+    "ROW_NUMBER" => "ROW_NUMBER() OVER (...)",
+    "RANK" => "RANK() OVER (...)",
+    "DENSE_RANK" => "DENSE_RANK() OVER (...)",
+    "PERCENT_RANK" => "PERCENT_RANK() OVER (...)",
+    "CUME_DIST" => "CUME_DIST() OVER (...)",
+    "NTILE" => "NTILE(n) OVER (...)",
+    "LAG" => "LAG(expr [, n [, default]]) OVER (...)",
+    "LEAD" => "LEAD(expr [, n [, default]]) OVER (...)",
+    "FIRST_VALUE" => "FIRST_VALUE(expr) OVER (...)",
+    "LAST_VALUE" => "LAST_VALUE(expr) OVER (...)",
+    "NTH_VALUE" => "NTH_VALUE(expr, n) OVER (...)",
+    // Control flow
+    "IF" => "IF(condition, value_if_true, value_if_false)",
+    "IFNULL" => "IFNULL(expr, value_if_null)",
+    "NULLIF" => "NULLIF(expr1, expr2)",
+    "COALESCE" => "COALESCE(value, ...)",
+    "GREATEST" => "GREATEST(value1, value2, ...)",
+    "LEAST" => "LEAST(value1, value2, ...)",
+    "ISNULL" => "ISNULL(expr)",
+    // Strings
+    "CONCAT" => "CONCAT(str1, str2, ...)",
+    "CONCAT_WS" => "CONCAT_WS(separator, str1, str2, ...)",
+    "LENGTH" => "LENGTH(str)",
+    "CHAR_LENGTH" => "CHAR_LENGTH(str)",
+    "LOWER" => "LOWER(str)",
+    "UPPER" => "UPPER(str)",
+    "LCASE" => "LCASE(str)",
+    "UCASE" => "UCASE(str)",
+    "SUBSTRING" => "SUBSTRING(str, pos [, len])",
+    "SUBSTR" => "SUBSTR(str, pos [, len])",
+    "SUBSTRING_INDEX" => "SUBSTRING_INDEX(str, delim, count)",
+    "LEFT" => "LEFT(str, len)",
+    "RIGHT" => "RIGHT(str, len)",
+    "TRIM" => "TRIM([{BOTH | LEADING | TRAILING} [remstr] FROM] str)",
+    "LTRIM" => "LTRIM(str)",
+    "RTRIM" => "RTRIM(str)",
+    "LPAD" => "LPAD(str, len, padstr)",
+    "RPAD" => "RPAD(str, len, padstr)",
+    "REPLACE" => "REPLACE(str, from_str, to_str)",
+    "REPEAT" => "REPEAT(str, count)",
+    "REVERSE" => "REVERSE(str)",
+    "LOCATE" => "LOCATE(substr, str [, pos])",
+    "INSTR" => "INSTR(str, substr)",
+    "POSITION" => "POSITION(substr IN str)",
+    "FIND_IN_SET" => "FIND_IN_SET(str, strlist)",
+    "FORMAT" => "FORMAT(x, d [, locale])",
+    "HEX" => "HEX(str_or_n)",
+    "UNHEX" => "UNHEX(str)",
+    "ASCII" => "ASCII(str)",
+    "CHAR" => "CHAR(n, ... [USING charset])",
+    "SPACE" => "SPACE(n)",
+    "STRCMP" => "STRCMP(expr1, expr2)",
+    "REGEXP_LIKE" => "REGEXP_LIKE(expr, pattern [, match_type])",
+    "REGEXP_REPLACE" => "REGEXP_REPLACE(expr, pattern, repl [, pos [, occurrence [, match_type]]])",
+    "REGEXP_SUBSTR" => "REGEXP_SUBSTR(expr, pattern [, pos [, occurrence [, match_type]]])",
+    "REGEXP_INSTR" => "REGEXP_INSTR(expr, pattern [, pos [, occurrence [, return_option [, match_type]]]])",
+    "TO_BASE64" => "TO_BASE64(str)",
+    "FROM_BASE64" => "FROM_BASE64(str)",
+    "MD5" => "MD5(str)",
+    "SHA1" => "SHA1(str)",
+    "SHA2" => "SHA2(str, hash_length)",
+    "UUID" => "UUID()",
+    "UUID_TO_BIN" => "UUID_TO_BIN(string_uuid [, swap_flag])",
+    "BIN_TO_UUID" => "BIN_TO_UUID(binary_uuid [, swap_flag])",
+    // Numbers
+    "ABS" => "ABS(x)",
+    "CEIL" => "CEIL(x)",
+    "CEILING" => "CEILING(x)",
+    "FLOOR" => "FLOOR(x)",
+    "ROUND" => "ROUND(x [, d])",
+    "TRUNCATE" => "TRUNCATE(x, d)",
+    "MOD" => "MOD(n, m)",
+    "POW" => "POW(x, y)",
+    "POWER" => "POWER(x, y)",
+    "SQRT" => "SQRT(x)",
+    "EXP" => "EXP(x)",
+    "LN" => "LN(x)",
+    "LOG" => "LOG([b,] x)",
+    "LOG2" => "LOG2(x)",
+    "LOG10" => "LOG10(x)",
+    "SIGN" => "SIGN(x)",
+    "RAND" => "RAND([seed])",
+    "PI" => "PI()",
+    "CONV" => "CONV(n, from_base, to_base)",
+    // Dates and times
+    "NOW" => "NOW([fsp])",
+    "CURDATE" => "CURDATE()",
+    "CURTIME" => "CURTIME([fsp])",
+    "CURRENT_TIMESTAMP" => "CURRENT_TIMESTAMP([fsp])",
+    "UTC_TIMESTAMP" => "UTC_TIMESTAMP([fsp])",
+    "UNIX_TIMESTAMP" => "UNIX_TIMESTAMP([date])",
+    "FROM_UNIXTIME" => "FROM_UNIXTIME(unix_timestamp [, format])",
+    "DATE" => "DATE(expr)",
+    "TIME" => "TIME(expr)",
+    "YEAR" => "YEAR(date)",
+    "MONTH" => "MONTH(date)",
+    "DAY" => "DAY(date)",
+    "HOUR" => "HOUR(time)",
+    "MINUTE" => "MINUTE(time)",
+    "SECOND" => "SECOND(time)",
+    "DAYOFWEEK" => "DAYOFWEEK(date)",
+    "DAYOFYEAR" => "DAYOFYEAR(date)",
+    "WEEK" => "WEEK(date [, mode])",
+    "DATE_ADD" => "DATE_ADD(date, INTERVAL expr unit)",
+    "DATE_SUB" => "DATE_SUB(date, INTERVAL expr unit)",
+    "ADDDATE" => "ADDDATE(date, INTERVAL expr unit)",
+    "SUBDATE" => "SUBDATE(date, INTERVAL expr unit)",
+    "DATEDIFF" => "DATEDIFF(expr1, expr2)",
+    "TIMEDIFF" => "TIMEDIFF(expr1, expr2)",
+    "TIMESTAMPDIFF" => "TIMESTAMPDIFF(unit, datetime_expr1, datetime_expr2)",
+    "TIMESTAMPADD" => "TIMESTAMPADD(unit, interval, datetime_expr)",
+    "DATE_FORMAT" => "DATE_FORMAT(date, format)",
+    "STR_TO_DATE" => "STR_TO_DATE(str, format)",
+    "LAST_DAY" => "LAST_DAY(date)",
+    "CONVERT_TZ" => "CONVERT_TZ(dt, from_tz, to_tz)",
+    "EXTRACT" => "EXTRACT(unit FROM date)",
+    // Casts
+    "CAST" => "CAST(expr AS type)",
+    "CONVERT" => "CONVERT(expr, type) | CONVERT(expr USING charset)",
+    // JSON
+    "JSON_EXTRACT" => "JSON_EXTRACT(json_doc, path [, path] ...)",
+    "JSON_UNQUOTE" => "JSON_UNQUOTE(json_val)",
+    "JSON_OBJECT" => "JSON_OBJECT([key, val [, key, val] ...])",
+    "JSON_ARRAY" => "JSON_ARRAY([val [, val] ...])",
+    "JSON_CONTAINS" => "JSON_CONTAINS(target, candidate [, path])",
+    "JSON_CONTAINS_PATH" => "JSON_CONTAINS_PATH(json_doc, one_or_all, path [, path] ...)",
+    "JSON_KEYS" => "JSON_KEYS(json_doc [, path])",
+    "JSON_LENGTH" => "JSON_LENGTH(json_doc [, path])",
+    "JSON_SET" => "JSON_SET(json_doc, path, val [, path, val] ...)",
+    "JSON_INSERT" => "JSON_INSERT(json_doc, path, val [, path, val] ...)",
+    "JSON_REPLACE" => "JSON_REPLACE(json_doc, path, val [, path, val] ...)",
+    "JSON_REMOVE" => "JSON_REMOVE(json_doc, path [, path] ...)",
+    "JSON_SEARCH" => "JSON_SEARCH(json_doc, one_or_all, search_str [, escape_char [, path] ...])",
+    "JSON_TYPE" => "JSON_TYPE(json_val)",
+    "JSON_VALID" => "JSON_VALID(val)",
+    "JSON_PRETTY" => "JSON_PRETTY(json_val)",
+    "JSON_TABLE" => "JSON_TABLE(expr, path COLUMNS (column_list)) [AS] alias",
+    "JSON_OVERLAPS" => "JSON_OVERLAPS(json_doc1, json_doc2)",
+    // Information
+    "DATABASE" => "DATABASE()",
+    "USER" => "USER()",
+    "CURRENT_USER" => "CURRENT_USER()",
+    "VERSION" => "VERSION()",
+    "CONNECTION_ID" => "CONNECTION_ID()",
+    "LAST_INSERT_ID" => "LAST_INSERT_ID([expr])",
+    "ROW_COUNT" => "ROW_COUNT()",
+    "FOUND_ROWS" => "FOUND_ROWS()",
+    "SLEEP" => "SLEEP(duration)",
+    "BENCHMARK" => "BENCHMARK(count, expr)",
+    "INET_ATON" => "INET_ATON(expr)",
+    "INET_NTOA" => "INET_NTOA(expr)",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word))
+}
+
+pub fn function(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|f| f.name.eq_ignore_ascii_case(name))
+}
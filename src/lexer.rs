@@ -0,0 +1,374 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Word,
+    QuotedIdentifier,
+    String,
+    Number,
+    Comment,
+    Whitespace,
+    Punctuation,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+    /// False for a string, quoted identifier or block comment missing its closing delimiter
+    pub terminated: bool,
+}
+
+impl Token {
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.start..self.end]
+    }
+
+    pub fn is_significant(&self) -> bool {
+        !matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Splits SQL into tokens with byte offsets. Unterminated strings and comments run to the end
+/// of the input, so the lexer never fails on a half-typed statement.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut terminated = true;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+                TokenKind::Whitespace
+            }
+            '#' => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+                TokenKind::Comment
+            }
+            '-' if input[start..].starts_with("-- ") || input[start..].starts_with("--\n") => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+                TokenKind::Comment
+            }
+            '/' if input[start..].starts_with("/*") => {
+                chars.next();
+                let mut previous = ' ';
+                terminated = false;
+                for (_, c) in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        terminated = true;
+                        break;
+                    }
+                    previous = c;
+                }
+                TokenKind::Comment
+            }
+            '\'' | '"' | '`' => {
+                let quote = c;
+                terminated = false;
+                while let Some((_, c)) = chars.next() {
+                    if c == '\\' && quote != '`' {
+                        chars.next();
+                    } else if c == quote {
+                        // A doubled quote is an escaped quote
+                        if chars.next_if(|(_, c)| *c == quote).is_none() {
+                            terminated = true;
+                            break;
+                        }
+                    }
+                }
+                if quote == '`' {
+                    TokenKind::QuotedIdentifier
+                } else {
+                    TokenKind::String
+                }
+            }
+            c if c.is_ascii_digit() => {
+                while chars
+                    .next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '.')
+                    .is_some()
+                {}
+                TokenKind::Number
+            }
+            c if is_word_char(c) => {
+                while chars.next_if(|(_, c)| is_word_char(*c)).is_some() {}
+                TokenKind::Word
+            }
+            _ => TokenKind::Punctuation,
+        };
+
+        let end = chars.peek().map(|(i, _)| *i).unwrap_or(input.len());
+        tokens.push(Token {
+            kind,
+            start,
+            end,
+            terminated,
+        });
+    }
+
+    tokens
+}
+
+/// Splits a script into statements at semicolons outside strings and comments, keeping the
+/// semicolons and dropping statements with nothing but whitespace and comments
+pub fn split_statements(input: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut significant = false;
+
+    for token in tokenize(input) {
+        if token.kind == TokenKind::Punctuation && token.text(input) == ";" {
+            if significant {
+                statements.push(input[start..token.end].trim());
+            }
+            start = token.end;
+            significant = false;
+        } else if token.is_significant() {
+            significant = true;
+        }
+    }
+    if significant {
+        statements.push(input[start..].trim());
+    }
+    statements
+}
+
+/// `input` without a trailing backslash command like `\G`, when the command is outside strings
+/// and comments, and `None` otherwise
+pub fn strip_backslash_command<'a>(input: &'a str, command: &str) -> Option<&'a str> {
+    let input = input.trim_end();
+    let sql = input.strip_suffix(command)?;
+    // The backslash is punctuation, so the command's letter has to be a word of its own
+    tokenize(input)
+        .last()
+        .is_some_and(|token| token.kind == TokenKind::Word && token.start == sql.len() + 1)
+        .then_some(sql)
+}
+
+fn closing_bracket(open: &str) -> Option<&'static str> {
+    match open {
+        "(" => Some(")"),
+        "[" => Some("]"),
+        "{" => Some("}"),
+        _ => None,
+    }
+}
+
+fn is_closing_bracket(text: &str) -> bool {
+    matches!(text, ")" | "]" | "}")
+}
+
+/// Pairs up brackets outside strings and comments, returning the matched pairs and the
+/// brackets left without a partner
+fn bracket_pairs(input: &str, tokens: &[Token]) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut open: Vec<&Token> = Vec::new();
+
+    for token in tokens.iter().filter(|t| t.kind == TokenKind::Punctuation) {
+        let text = token.text(input);
+        if closing_bracket(text).is_some() {
+            open.push(token);
+        } else if is_closing_bracket(text) {
+            match open.last() {
+                Some(last) if closing_bracket(last.text(input)) == Some(text) => {
+                    pairs.push((last.start, token.start));
+                    open.pop();
+                }
+                _ => unmatched.push(token.start),
+            }
+        }
+    }
+    unmatched.extend(open.iter().map(|t| t.start));
+    (pairs, unmatched)
+}
+
+/// Where the bracket or quote matching the one at `position` is
+pub fn matching_delimiter(input: &str, position: usize) -> Option<usize> {
+    let tokens = tokenize(input);
+    let token = tokens
+        .iter()
+        .find(|t| t.start <= position && position < t.end)?;
+    match token.kind {
+        TokenKind::String | TokenKind::QuotedIdentifier if token.terminated => {
+            let last = token.end - 1;
+            if position == token.start {
+                Some(last)
+            } else if position == last {
+                Some(token.start)
+            } else {
+                None
+            }
+        }
+        TokenKind::Punctuation => {
+            let (pairs, _) = bracket_pairs(input, &tokens);
+            pairs.iter().find_map(|&(open, close)| {
+                if open == position {
+                    Some(close)
+                } else if close == position {
+                    Some(open)
+                } else {
+                    None
+                }
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The first unterminated string or comment, or unbalanced bracket, and what is wrong with it
+pub fn find_imbalance(input: &str) -> Option<(usize, String)> {
+    let tokens = tokenize(input);
+    if let Some(token) = tokens.iter().find(|t| !t.terminated) {
+        let problem = match token.kind {
+            TokenKind::Comment => "Unterminated comment".to_string(),
+            TokenKind::QuotedIdentifier => "Unterminated quoted identifier".to_string(),
+            _ => format!(
+                "Unterminated string starting with {}",
+                &input[token.start..token.start + 1]
+            ),
+        };
+        return Some((token.start, problem));
+    }
+
+    let (_, unmatched) = bracket_pairs(input, &tokens);
+    let position = unmatched.into_iter().min()?;
+    let bracket = &input[position..position + 1];
+    let problem = match closing_bracket(bracket) {
+        Some(close) => format!("Unclosed {}, expected a {}", bracket, close),
+        None => format!("Unmatched {}", bracket),
+    };
+    Some((position, problem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Kind and text of every token but whitespace
+    fn tokens(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, t.text(input)))
+            .collect()
+    }
+
+    #[test]
+    fn strip_backslash_command_skips_strings_and_comments() {
+        assert_eq!(
+            strip_backslash_command("select 1 \\e ", "\\e"),
+            Some("select 1 ")
+        );
+        assert_eq!(strip_backslash_command("\\e", "\\e"), Some(""));
+        assert_eq!(strip_backslash_command("select '\\e'", "\\e"), None);
+        assert_eq!(strip_backslash_command("select '\\e", "\\e"), None);
+        assert_eq!(strip_backslash_command("select 1 -- \\e", "\\e"), None);
+        assert_eq!(strip_backslash_command("select 1 /* \\e", "\\e"), None);
+        assert_eq!(strip_backslash_command("select 1", "\\e"), None);
+    }
+
+    #[test]
+    fn tokenize_keeps_doubled_quotes_in_the_string() {
+        assert_eq!(
+            tokens("select 'it''s', `a``b`"),
+            [
+                (TokenKind::Word, "select"),
+                (TokenKind::String, "'it''s'"),
+                (TokenKind::Punctuation, ","),
+                (TokenKind::QuotedIdentifier, "`a``b`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_skips_backslash_escapes_in_strings_only() {
+        assert_eq!(
+            tokens(r#"'a\'b' "c\"d""#),
+            [
+                (TokenKind::String, r"'a\'b'"),
+                (TokenKind::String, r#""c\"d""#)
+            ]
+        );
+        // Backslashes are literal in quoted identifiers
+        assert_eq!(
+            tokens(r"`a\` b"),
+            [
+                (TokenKind::QuotedIdentifier, r"`a\`"),
+                (TokenKind::Word, "b")
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_needs_whitespace_after_double_dash_for_a_comment() {
+        assert_eq!(
+            tokens("1 -- note\n2"),
+            [
+                (TokenKind::Number, "1"),
+                (TokenKind::Comment, "-- note"),
+                (TokenKind::Number, "2"),
+            ]
+        );
+        assert_eq!(
+            tokens("1 --x"),
+            [
+                (TokenKind::Number, "1"),
+                (TokenKind::Punctuation, "-"),
+                (TokenKind::Punctuation, "-"),
+                (TokenKind::Word, "x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_marks_unterminated_strings_and_comments() {
+        let unterminated = |input| tokenize(input).iter().any(|t| !t.terminated);
+        assert!(unterminated("select 'abc"));
+        assert!(unterminated("select /* abc"));
+        assert!(unterminated("select `abc"));
+        assert!(!unterminated("select 'abc' /* def */"));
+    }
+
+    #[test]
+    fn split_statements_ignores_semicolons_in_strings_and_comments() {
+        assert_eq!(
+            split_statements("select ';'; -- a; b\nselect \"x;y\" /* ; */;"),
+            ["select ';';", "-- a; b\nselect \"x;y\" /* ; */;"]
+        );
+    }
+
+    #[test]
+    fn split_statements_drops_empty_statements() {
+        assert_eq!(split_statements(";; select 1 ;  -- done\n"), ["select 1 ;"]);
+        assert_eq!(split_statements("select 1"), ["select 1"]);
+    }
+
+    #[test]
+    fn find_imbalance_reports_unclosed_brackets() {
+        assert_eq!(
+            find_imbalance("select (1, [2)"),
+            Some((7, "Unclosed (, expected a )".to_string()))
+        );
+        assert_eq!(
+            find_imbalance("select 1)"),
+            Some((8, "Unmatched )".to_string()))
+        );
+        assert_eq!(find_imbalance("select ('(', \")\")"), None);
+    }
+
+    #[test]
+    fn find_imbalance_reports_unterminated_strings_first() {
+        assert_eq!(
+            find_imbalance("select ( 'abc"),
+            Some((9, "Unterminated string starting with '".to_string()))
+        );
+        assert_eq!(
+            find_imbalance("select 1 /* x"),
+            Some((9, "Unterminated comment".to_string()))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use console::{measure_text_width, pad_str, style, truncate_str, Alignment};
+
+use crate::completion::{Candidate, Completion};
+
+/// Number of candidates visible at once
+const MENU_HEIGHT: usize = 8;
+/// Candidate names longer than this are truncated in the menu
+const MAX_TEXT_WIDTH: usize = 40;
+
+/// Popup listing completion candidates below the prompt
+#[derive(Debug)]
+pub struct CompletionMenu {
+    /// Byte offset in the input where the completed word starts
+    pub start: usize,
+    candidates: Vec<Candidate>,
+    selected: usize,
+    offset: usize,
+}
+
+impl CompletionMenu {
+    pub fn new(completion: Completion) -> Self {
+        Self {
+            start: completion.start,
+            candidates: completion.candidates,
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn selected(&self) -> &Candidate {
+        &self.candidates[self.selected]
+    }
+
+    pub fn next(&mut self) {
+        self.select((self.selected + 1) % self.candidates.len());
+    }
+
+    pub fn previous(&mut self) {
+        self.select((self.selected + self.candidates.len() - 1) % self.candidates.len());
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        // Scroll the visible window so the selection stays in view
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + MENU_HEIGHT {
+            self.offset = self.selected + 1 - MENU_HEIGHT;
+        }
+    }
+
+    /// Lines to print below the prompt, indented to `column` and cut to `width`
+    pub fn render(&self, column: usize, width: usize) -> Vec<String> {
+        let visible =
+            &self.candidates[self.offset..(self.offset + MENU_HEIGHT).min(self.candidates.len())];
+        let text_width = visible
+            .iter()
+            .map(|c| measure_text_width(&c.text).min(MAX_TEXT_WIDTH))
+            .max()
+            .unwrap_or(0);
+        let kind_width = visible
+            .iter()
+            .map(|c| c.kind.label().len())
+            .max()
+            .unwrap_or(0);
+        // Shift the menu left when it would run off the right edge
+        let column = column.min(width.saturating_sub(text_width + kind_width + 4));
+
+        let mut lines = Vec::new();
+        for (i, candidate) in visible.iter().enumerate() {
+            let text = truncate_str(&candidate.text, MAX_TEXT_WIDTH, "…");
+            let mut line = format!(
+                " {}  {:<kind_width$} ",
+                pad_str(&text, text_width, Alignment::Left, None),
+                candidate.kind.label()
+            );
+            if let Some(detail) = &candidate.detail {
+                line = format!("{line}{detail} ");
+            }
+            let line = truncate_str(&line, width.saturating_sub(column), "…").to_string();
+
+            let line = if self.offset + i == self.selected {
+                style(line).reverse().to_string()
+            } else {
+                style(line).dim().to_string()
+            };
+            lines.push(format!("{}{}", " ".repeat(column), line));
+        }
+
+        if self.candidates.len() > MENU_HEIGHT {
+            lines.push(format!(
+                "{}{}",
+                " ".repeat(column),
+                style(format!(" {}/{} ", self.selected + 1, self.candidates.len())).dim()
+            ));
+        }
+
+        lines
+    }
+}
@@ -0,0 +1,72 @@
+use console::Term;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+use crate::signal::ignore_interrupts;
+
+/// Pager used when neither `\P` nor `$PAGER` names one
+const DEFAULT_PAGER: &str = "less -SRFX";
+
+/// `$PAGER`, or `less` chopping long lines and keeping colors
+pub fn default_command() -> String {
+    std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string())
+}
+
+/// The pager named by `--pager` or `\P`, falling back to the default one when it is empty
+pub fn command(pager: &str) -> String {
+    match pager.trim() {
+        "" => default_command(),
+        pager => pager.to_string(),
+    }
+}
+
+/// Prints `fitted`, or shows the full `text` through `pager` when there is one and `fitted` does
+/// not fit on the terminal
+pub fn show(pager: Option<&str>, text: &str, fitted: &str) {
+    match pager {
+        Some(pager) if needs_paging(fitted) => {
+            if let Err(e) = page(pager, text) {
+                eprintln!("[-] Could not run pager {}: {}", pager, e);
+                println!("{}", fitted);
+            }
+        }
+        _ => println!("{}", fitted),
+    }
+}
+
+/// Whether `text` is too tall to show on the terminal without scrolling the prompt away
+pub fn needs_paging(text: &str) -> bool {
+    let term = Term::stdout();
+    if !term.is_term() {
+        return false;
+    }
+    let (rows, _) = term.size();
+    text.lines().count() >= rows as usize
+}
+
+/// Shows `text` through the shell command `pager` and waits for it to exit
+pub fn page(pager: &str, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(pager).stdin(Stdio::piped());
+    // Like git, have less pass colors through unless the user configured it
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRSX");
+    }
+    let mut child = command.spawn()?;
+    // Ctrl-C is for the pager, as with git's, so it mustn't end the session too
+    let _interrupts = ignore_interrupts();
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager before reading everything closes the pipe
+        if let Err(e) = writeln!(stdin, "{}", text) {
+            if e.kind() != ErrorKind::BrokenPipe {
+                return Err(e.into());
+            }
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
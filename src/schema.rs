@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{MySqlConnection, Row};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::connector::get_current_database;
+use crate::trie::Trie;
+
+/// Schema shared between the REPL and the background loader
+pub type SharedSchema = Arc<std::sync::Mutex<Schema>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    pub data_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Table {
+    pub name: String,
+    pub is_view: bool,
+    pub columns: Vec<Column>,
+    pub indexes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Routine {
+    pub name: String,
+    pub kind: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Schema {
+    pub database: Option<String>,
+    pub databases: Vec<String>,
+    /// Names of the tables in every database, for completing `db.` qualifiers
+    #[serde(default)]
+    pub database_tables: BTreeMap<String, Vec<String>>,
+    pub tables: Vec<Table>,
+    pub routines: Vec<Routine>,
+    #[serde(skip)]
+    pub symbols: Trie,
+    /// Why the last refresh failed, for the session to report at the next prompt
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl Schema {
+    pub fn empty(database: Option<&str>) -> Self {
+        Self {
+            database: database.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the whole schema of the current database with a fixed number of queries
+    pub async fn load(connection: Arc<Mutex<MySqlConnection>>) -> Result<Self, sqlx::Error> {
+        let database = get_current_database(connection.clone()).await?;
+        let mut schema = Schema::empty(database.as_deref());
+
+        let databases =
+            sqlx::query("SELECT CAST(SCHEMA_NAME AS CHAR) FROM information_schema.SCHEMATA")
+                .fetch_all(&mut *connection.lock().await)
+                .await?;
+        for row in &databases {
+            schema.databases.push(row.try_get(0)?);
+        }
+
+        let tables = sqlx::query(
+            "SELECT CAST(TABLE_SCHEMA AS CHAR), CAST(TABLE_NAME AS CHAR) \
+             FROM information_schema.TABLES ORDER BY TABLE_SCHEMA, TABLE_NAME",
+        )
+        .fetch_all(&mut *connection.lock().await)
+        .await?;
+        for row in &tables {
+            schema
+                .database_tables
+                .entry(row.try_get(0)?)
+                .or_default()
+                .push(row.try_get(1)?);
+        }
+
+        let Some(database) = database else {
+            return Ok(schema);
+        };
+
+        // information_schema columns may come back as binary strings, so cast everything to CHAR
+        let columns = sqlx::query(
+            "SELECT CAST(TABLE_NAME AS CHAR), CAST(COLUMN_NAME AS CHAR), CAST(COLUMN_TYPE AS CHAR) \
+             FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = ? \
+             ORDER BY TABLE_NAME, ORDINAL_POSITION",
+        )
+        .bind(&database)
+        .fetch_all(&mut *connection.lock().await)
+        .await?;
+        for row in &columns {
+            let table_name = row.try_get::<String, _>(0)?;
+            let column = Column {
+                name: row.try_get(1)?,
+                data_type: row.try_get(2)?,
+            };
+            match schema.tables.last_mut() {
+                Some(table) if table.name == table_name => table.columns.push(column),
+                _ => schema.tables.push(Table {
+                    name: table_name,
+                    is_view: false,
+                    columns: vec![column],
+                    indexes: Vec::new(),
+                }),
+            }
+        }
+
+        let views = sqlx::query(
+            "SELECT CAST(TABLE_NAME AS CHAR) FROM information_schema.VIEWS WHERE TABLE_SCHEMA = ?",
+        )
+        .bind(&database)
+        .fetch_all(&mut *connection.lock().await)
+        .await?;
+        for row in &views {
+            let view_name = row.try_get::<String, _>(0)?;
+            if let Some(table) = schema.table_mut(&view_name) {
+                table.is_view = true;
+            }
+        }
+
+        let indexes = sqlx::query(
+            "SELECT DISTINCT CAST(TABLE_NAME AS CHAR), CAST(INDEX_NAME AS CHAR) \
+             FROM information_schema.STATISTICS WHERE TABLE_SCHEMA = ?",
+        )
+        .bind(&database)
+        .fetch_all(&mut *connection.lock().await)
+        .await?;
+        for row in &indexes {
+            let table_name = row.try_get::<String, _>(0)?;
+            if let Some(table) = schema.table_mut(&table_name) {
+                table.indexes.push(row.try_get(1)?);
+            }
+        }
+
+        let routines = sqlx::query(
+            "SELECT CAST(ROUTINE_NAME AS CHAR), CAST(ROUTINE_TYPE AS CHAR) \
+             FROM information_schema.ROUTINES WHERE ROUTINE_SCHEMA = ?",
+        )
+        .bind(&database)
+        .fetch_all(&mut *connection.lock().await)
+        .await?;
+        for row in &routines {
+            schema.routines.push(Routine {
+                name: row.try_get(0)?,
+                kind: row.try_get(1)?,
+            });
+        }
+
+        schema.build_symbols();
+        Ok(schema)
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The database named `name`, ignoring case
+    pub fn database(&self, name: &str) -> Option<&str> {
+        self.databases
+            .iter()
+            .find(|d| d.eq_ignore_ascii_case(name))
+            .map(String::as_str)
+    }
+
+    /// Whether `name` is a database, table, column or routine
+    pub fn is_symbol(&self, name: &str) -> bool {
+        self.symbols.contains_ignore_case(name) || self.database(name).is_some()
+    }
+
+    fn table_mut(&mut self, name: &str) -> Option<&mut Table> {
+        self.tables.iter_mut().find(|t| t.name == name)
+    }
+
+    fn build_symbols(&mut self) {
+        let mut symbols = Trie::new();
+        for table in &self.tables {
+            symbols.insert(&table.name);
+            for column in &table.columns {
+                symbols.insert(&column.name);
+            }
+        }
+        for routine in &self.routines {
+            symbols.insert(&routine.name);
+        }
+        self.symbols = symbols;
+    }
+
+    pub fn cache_path(server: &str, database: &str) -> PathBuf {
+        let home = &std::env::var("HOME").unwrap();
+        Path::new(home)
+            .join(".cache/oxisql/schema")
+            .join(server)
+            .join(format!("{database}.json"))
+    }
+
+    pub fn from_file(filename: &Path) -> Option<Schema> {
+        let file = File::open(filename).ok()?;
+        let mut schema: Schema = serde_json::from_reader(file).ok()?;
+        schema.build_symbols();
+        Some(schema)
+    }
+
+    pub fn save(&self, filename: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(filename.parent().unwrap())?;
+        let file = File::create(filename)?;
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+}
+
+/// Swaps in the cached schema for `database` and refreshes it from the server in the background
+pub fn refresh_schema(
+    connection: Arc<Mutex<MySqlConnection>>,
+    schema: SharedSchema,
+    server: &str,
+    database: Option<&str>,
+) {
+    let cached = database
+        .and_then(|database| Schema::from_file(&Schema::cache_path(server, database)))
+        .unwrap_or_else(|| Schema::empty(database));
+    *schema.lock().unwrap() = cached;
+
+    let server = server.to_string();
+    let database = database.map(String::from);
+    tokio::spawn(async move {
+        match Schema::load(connection).await {
+            Ok(mut loaded) => {
+                if let Some(database) = &loaded.database {
+                    if let Err(e) = loaded.save(&Schema::cache_path(&server, database)) {
+                        loaded.error = Some(format!("Could not cache schema: {}", e));
+                    }
+                }
+
+                // A USE issued while loading makes this result stale
+                let mut schema = schema.lock().unwrap();
+                if schema.database == loaded.database {
+                    *schema = loaded;
+                }
+            }
+            Err(e) => {
+                // Keep completing from the cached schema
+                let mut schema = schema.lock().unwrap();
+                if schema.database == database {
+                    schema.error = Some(format!("Could not load schema: {}", e));
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,246 @@
+use chrono::Utc;
+use sqlx::MySqlConnection;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use crate::commands::{self, Flow, Terminator};
+use crate::completion::SqlHelper;
+use crate::connector::{MySqlOutputTable, MySqlResult};
+use crate::editor::{EditingMode, LineEditor};
+use crate::formatter::{terminal_width, Fit};
+use crate::highlight::{Highlighter, Theme};
+use crate::history::{History, HistoryEntry};
+use crate::lexer::split_statements;
+use crate::pager;
+use crate::schema::{refresh_schema, Schema, SharedSchema};
+use crate::tee::Tee;
+use crate::MySqlArgs;
+
+pub const PROMPT: &str = "oxisql> ";
+
+/// State of an interactive session, shared by the REPL loop and the meta-commands
+pub struct Session {
+    pub connection: Arc<Mutex<MySqlConnection>>,
+    pub args: MySqlArgs,
+    pub current_database: Option<String>,
+    pub schema: SharedSchema,
+    pub history: History,
+    pub editor: LineEditor,
+    helper: SqlHelper,
+    pub tee: Option<Tee>,
+    /// Result of the last query that returned rows, for `\view`
+    pub last_result: Option<MySqlOutputTable>,
+    /// Command that output taller than the terminal is piped to, set with `\P`
+    pub pager: Option<String>,
+}
+
+impl Session {
+    pub fn new(connection: Arc<Mutex<MySqlConnection>>, args: MySqlArgs) -> Self {
+        let schema: SharedSchema = Arc::new(std::sync::Mutex::new(Schema::default()));
+        let mut editor = LineEditor::new(PROMPT);
+        if let Some(mode) = EditingMode::from_inputrc() {
+            editor.set_editing_mode(mode);
+        }
+        editor.set_auto_pairs(args.auto_pairs);
+
+        let tee = args.tee.clone();
+        let pager = args.pager.clone();
+        let mut session = Self {
+            connection,
+            current_database: args.database.clone(),
+            helper: SqlHelper {
+                schema: schema.clone(),
+                highlighter: Highlighter::new(&Theme::load(&Theme::default_path())),
+            },
+            schema,
+            history: History::open(&History::default_path()),
+            editor,
+            args,
+            tee: None,
+            pager: None,
+            last_result: None,
+        };
+        session.refresh_schema();
+        if let Some(path) = tee {
+            session.set_tee(&path);
+        }
+        if let Some(pager) = pager {
+            session.set_pager(&pager);
+        }
+        session
+    }
+
+    /// User, host and port as a directory name for the schema cache, since what a schema shows
+    /// depends on the user's grants
+    fn server(&self) -> String {
+        format!("{}@{}_{}", self.args.user, self.args.host, self.args.port)
+    }
+
+    pub fn refresh_schema(&self) {
+        refresh_schema(
+            self.connection.clone(),
+            self.schema.clone(),
+            &self.server(),
+            self.current_database.as_deref(),
+        );
+    }
+
+    pub async fn run(&mut self) {
+        loop {
+            // The background loader can't print while the prompt is being edited
+            if let Some(e) = self.schema.lock().unwrap().error.take() {
+                eprintln!("[-] {}", e);
+            }
+            let Some(input) = self.editor.read_line(&self.helper, &mut self.history) else {
+                break;
+            };
+            if self.handle(&input).await == Flow::Quit {
+                break;
+            }
+        }
+        println!("Bye!");
+    }
+
+    /// Runs a line from the prompt, which is either a meta-command or SQL
+    pub async fn handle(&mut self, input: &str) -> Flow {
+        let input = input.trim();
+        match commands::parse(input) {
+            Some(Ok((command, arguments))) => {
+                self.log_statement(input);
+                return (command.run)(self, arguments).await;
+            }
+            Some(Err(e)) => {
+                eprintln!("[-] {}", e);
+                return Flow::Continue;
+            }
+            None => {}
+        }
+
+        let (sql, terminator) = commands::split_terminator(input);
+        if terminator != Terminator::Cancel {
+            self.run_statements(sql, terminator == Terminator::Vertical)
+                .await;
+        }
+        Flow::Continue
+    }
+
+    /// Runs each statement of a script in turn, stopping at the first that fails
+    pub async fn run_statements(&mut self, sql: &str, vertical: bool) -> bool {
+        for statement in split_statements(sql) {
+            if !self.execute(statement, vertical).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Runs a single statement, records it in history and prints its result
+    pub async fn execute(&mut self, statement: &str, vertical: bool) -> bool {
+        self.log_statement(statement);
+        let start_time = Instant::now();
+        let result = MySqlResult::parse_query(statement.to_string(), self.connection.clone()).await;
+        let end_time = Instant::now();
+
+        let entry = HistoryEntry {
+            timestamp: Some(Utc::now()),
+            host: Some(format!("{}:{}", self.args.host, self.args.port)),
+            database: self.current_database.clone(),
+            duration_ms: Some(end_time.duration_since(start_time).as_millis()),
+            success: Some(result.is_ok()),
+            ..HistoryEntry::new(statement)
+        };
+        if let Err(e) = self.history.append(entry) {
+            eprintln!("[-] Could not save history: {}", e);
+        }
+
+        match result {
+            Ok(value) => {
+                if vertical {
+                    self.print(&value.vertical());
+                } else {
+                    self.print_fitted(&value);
+                }
+                self.print(&format!(
+                    "Elapsed time: {}ms",
+                    end_time.duration_since(start_time).as_millis()
+                ));
+
+                match value {
+                    MySqlResult::DatabaseChanged(database) if database != self.current_database => {
+                        self.current_database = database;
+                        self.refresh_schema();
+                    }
+                    MySqlResult::Table(table) => self.last_result = Some(table),
+                    _ => {}
+                }
+                true
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                self.log(&format!("Error: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Prints output, through the pager when it does not fit on the terminal, and copies it to
+    /// the tee file
+    pub fn print(&mut self, text: &str) {
+        self.show(text, None);
+    }
+
+    /// Prints output like `print`, narrowed to the terminal's width unless it goes to the pager
+    pub fn print_fitted(&mut self, output: &dyn Fit) {
+        let fitted = terminal_width().map(|width| output.fit(width));
+        self.show(
+            output.to_string().trim_end(),
+            fitted.as_deref().map(str::trim_end),
+        );
+    }
+
+    /// Pages `text` or prints `fitted`, its form for the terminal, and logs `text` in full
+    fn show(&mut self, text: &str, fitted: Option<&str>) {
+        pager::show(self.pager.as_deref(), text, fitted.unwrap_or(text));
+        self.log(text);
+    }
+
+    /// Pipes long output to `pager`, or to the default pager when it is empty
+    pub fn set_pager(&mut self, pager: &str) {
+        let pager = pager::command(pager);
+        println!("[+] PAGER set to '{}'", pager);
+        self.pager = Some(pager);
+    }
+
+    /// Copies a line to the tee file alone
+    fn log(&mut self, text: &str) {
+        if let Some(tee) = &mut self.tee {
+            if let Err(e) = tee.write(text) {
+                eprintln!("[-] Could not write to {}: {}", tee.path().display(), e);
+                self.tee = None;
+            }
+        }
+    }
+
+    /// Copies a statement or command to the tee file with the time it was run
+    fn log_statement(&mut self, statement: &str) {
+        if let Some(tee) = &mut self.tee {
+            if let Err(e) = tee.statement(PROMPT, statement) {
+                eprintln!("[-] Could not write to {}: {}", tee.path().display(), e);
+                self.tee = None;
+            }
+        }
+    }
+
+    /// Starts copying statements and their output to `path`
+    pub fn set_tee(&mut self, path: &Path) {
+        match Tee::open(path, &format!("{}:{}", self.args.host, self.args.port)) {
+            Ok(tee) => {
+                println!("[+] Logging to file '{}'", path.display());
+                self.tee = Some(tee);
+            }
+            Err(e) => eprintln!("[-] Could not open {}: {}", path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::mem::MaybeUninit;
+
+extern "C" fn ignore(_: libc::c_int) {}
+
+/// Keeps Ctrl-C from killing oxisql until dropped, then restores the previous SIGINT action
+pub struct InterruptGuard {
+    previous: libc::sigaction,
+}
+
+/// Catches SIGINT with a handler that does nothing. Unlike `SIG_IGN`, a caught signal goes back
+/// to its default action in programs run meanwhile, so a pager or shell command can still be
+/// interrupted, and console's `read_key` returns an `Interrupted` error instead of exiting.
+pub fn ignore_interrupts() -> InterruptGuard {
+    // SAFETY: the actions are fully initialized before use and the handler is async-signal-safe
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = ignore as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous = MaybeUninit::<libc::sigaction>::zeroed();
+        libc::sigaction(libc::SIGINT, &action, previous.as_mut_ptr());
+        InterruptGuard {
+            previous: previous.assume_init(),
+        }
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        // SAFETY: `previous` was filled in by sigaction
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.previous, std::ptr::null_mut());
+        }
+    }
+}
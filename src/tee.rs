@@ -0,0 +1,45 @@
+use chrono::Local;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Log file that statements and their results are copied to, set with `\T` or `--tee`
+pub struct Tee {
+    path: PathBuf,
+    file: File,
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+impl Tee {
+    /// Opens `path` for appending, so one file can collect several sessions
+    pub fn open(path: &Path, server: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut tee = Self {
+            path: path.to_path_buf(),
+            file,
+        };
+        tee.write(&format!(
+            "-- [{}] Logging session on {}",
+            timestamp(),
+            server
+        ))?;
+        Ok(tee)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a statement as typed, stamped with the time it was run
+    pub fn statement(&mut self, prompt: &str, statement: &str) -> std::io::Result<()> {
+        self.write(&format!("[{}] {}{}", timestamp(), prompt, statement))
+    }
+
+    /// Writes output without its colors
+    pub fn write(&mut self, text: &str) -> std::io::Result<()> {
+        writeln!(self.file, "{}", console::strip_ansi_codes(text))
+    }
+}
@@ -1,30 +1,22 @@
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use serde::{Serialize, Deserialize};
 use std::fs::File;
 use std::path::Path;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Trie {
     root: TrieNode,
-    index : usize
+    index: usize,
 }
 
 impl Trie {
     pub fn new() -> Trie {
         Trie {
             root: TrieNode::new(),
-            index: 0
+            index: 0,
         }
     }
 
-    pub fn from_vec(words: Vec<String>) -> Trie {
-        let mut trie = Trie::new();
-        for word in words {
-            trie.insert(&word);
-        }
-        trie
-    }
-
     pub fn from_file(filename: &Path) -> Option<Trie> {
         let file = File::open(filename);
         if let Ok(file) = file {
@@ -34,15 +26,7 @@ impl Trie {
             }
         }
 
-        return None;
-    }
-
-    pub fn save(&self, filename: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        // create all parent directories
-        std::fs::create_dir_all(filename.parent().unwrap())?;
-        let file = File::create(filename).expect("Unable to create file");
-        serde_json::to_writer(file, self).expect("Unable to write data");
-        Ok(())
+        None
     }
 
     pub fn insert(&mut self, word: &str) {
@@ -50,17 +34,29 @@ impl Trie {
         self.index += 1;
     }
 
-    pub fn search_all(&self, prefix: &str) -> Vec<String> {
-        let mut vector = self.root.search_all(prefix);
+    pub fn search_ignore_case(&self, prefix: &str) -> Vec<String> {
+        let prefix: Vec<char> = prefix.chars().flat_map(char::to_lowercase).collect();
+        let mut vector = vec![];
+        self.root.search_ignore_case(&prefix, &mut vector);
+        vector.sort_by_key(|word| self.get_index(word));
+        vector
+    }
 
-        // Sort by index
-        vector.sort_by(|a, b| {
-            let a_index = self.get_index(a).unwrap();
-            let b_index = self.get_index(b).unwrap();
-            a_index.cmp(&b_index)
-        });
+    pub fn contains_ignore_case(&self, word: &str) -> bool {
+        let word: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+        self.root.contains_ignore_case(&word)
+    }
 
-        vector
+    /// Returns every word the pattern is a subsequence of, best matches first
+    pub fn fuzzy_search(&self, pattern: &str) -> Vec<String> {
+        let mut scored: Vec<(i64, usize, String)> = vec![];
+        for word in self.root.search_all("") {
+            if let Some(score) = fuzzy_score(pattern, &word) {
+                scored.push((score, self.get_index(&word).unwrap_or(0), word));
+            }
+        }
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, _, word)| word).collect()
     }
 
     fn get_index(&self, word: &str) -> Option<usize> {
@@ -76,11 +72,17 @@ impl Trie {
     }
 }
 
+impl Default for Trie {
+    fn default() -> Self {
+        Trie::new()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct TrieNode {
     children: HashMap<char, TrieNode>,
     word: Option<String>,
-    index: Option<usize>
+    index: Option<usize>,
 }
 
 impl TrieNode {
@@ -88,7 +90,7 @@ impl TrieNode {
         TrieNode {
             children: HashMap::new(),
             word: None,
-            index: None
+            index: None,
         }
     }
 
@@ -115,6 +117,28 @@ impl TrieNode {
         words.clone()
     }
 
+    fn search_ignore_case(&self, prefix: &[char], words: &mut Vec<String>) {
+        match prefix.split_first() {
+            None => self.traverse(words),
+            Some((c, rest)) => {
+                for (key, child) in &self.children {
+                    if key.to_lowercase().eq(std::iter::once(*c)) {
+                        child.search_ignore_case(rest, words);
+                    }
+                }
+            }
+        }
+    }
+
+    fn contains_ignore_case(&self, word: &[char]) -> bool {
+        match word.split_first() {
+            None => self.word.is_some(),
+            Some((c, rest)) => self.children.iter().any(|(key, child)| {
+                key.to_lowercase().eq(std::iter::once(*c)) && child.contains_ignore_case(rest)
+            }),
+        }
+    }
+
     fn traverse(&self, words: &mut Vec<String>) {
         if let Some(word) = &self.word {
             words.push(word.clone());
@@ -124,3 +148,104 @@ impl TrieNode {
         }
     }
 }
+
+const MATCH_SCORE: i64 = 1;
+const CONSECUTIVE_BONUS: i64 = 5;
+const BOUNDARY_BONUS: i64 = 8;
+const GAP_PENALTY: i64 = 1;
+
+fn is_boundary(chars: &[char], i: usize) -> bool {
+    i == 0
+        || !chars[i - 1].is_alphanumeric()
+        || (chars[i - 1].is_lowercase() && chars[i].is_uppercase())
+}
+
+/// Scores how well `pattern` matches `candidate` as a case-insensitive subsequence, or `None`
+/// when it doesn't match at all. Consecutive characters and characters at word boundaries
+/// (`usrid` against `user_id`) score higher, gaps and a late first match score lower.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    // best[j] is the best score so far with the last matched pattern character at chars[j]
+    let mut best: Vec<Option<i64>> = vec![None; chars.len()];
+    for (i, p) in pattern.iter().enumerate() {
+        let mut current: Vec<Option<i64>> = vec![None; chars.len()];
+        let mut best_before_gap: Option<i64> = None;
+        for j in 0..chars.len() {
+            if j >= 2 {
+                best_before_gap = best_before_gap.max(best[j - 2]);
+            }
+            if lower[j] != *p {
+                continue;
+            }
+
+            let bonus = MATCH_SCORE
+                + if is_boundary(&chars, j) {
+                    BOUNDARY_BONUS
+                } else {
+                    0
+                };
+            current[j] = if i == 0 {
+                Some(bonus - (j as i64).min(BOUNDARY_BONUS))
+            } else {
+                let consecutive = if j >= 1 {
+                    best[j - 1].map(|s| s + CONSECUTIVE_BONUS)
+                } else {
+                    None
+                };
+                let gapped = best_before_gap.map(|s| s - GAP_PENALTY);
+                consecutive.max(gapped).map(|s| s + bonus)
+            };
+        }
+        best = current;
+    }
+
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    best.into_iter().flatten().max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score_prefers_word_boundaries() {
+        let user_id = fuzzy_score("usrid", "user_id").unwrap();
+        let username = fuzzy_score("usrid", "users_grid");
+        assert!(username.is_none_or(|score| user_id > score));
+        assert!(fuzzy_score("ui", "user_id").unwrap() > fuzzy_score("ui", "build").unwrap());
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_consecutive_and_early_matches() {
+        assert!(fuzzy_score("ord", "orders").unwrap() > fuzzy_score("ord", "oxrxd").unwrap());
+        assert!(fuzzy_score("ord", "orders").unwrap() > fuzzy_score("ord", "my_orders").unwrap());
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case() {
+        assert_eq!(fuzzy_score("UID", "user_id"), fuzzy_score("uid", "user_id"));
+        assert!(fuzzy_score("userid", "UserId").is_some());
+    }
+
+    #[test]
+    fn fuzzy_score_accepts_empty_pattern() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("", ""), Some(0));
+    }
+
+    #[test]
+    fn fuzzy_score_rejects_non_matches() {
+        assert_eq!(fuzzy_score("xyz", "user_id"), None);
+        assert_eq!(fuzzy_score("diu", "user_id"), None);
+        assert_eq!(fuzzy_score("users", "user"), None);
+        assert_eq!(fuzzy_score("a", ""), None);
+    }
+}
@@ -0,0 +1,585 @@
+use console::Key;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::buffer::Buffer;
+use crate::editor::{Command, EmacsKeymap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Insert,
+    Normal,
+}
+
+/// A key that needs the next character to complete it
+#[derive(Debug, Clone, Copy)]
+enum Pending {
+    /// `f`, `F`, `t` or `T`
+    Find(char),
+    Replace,
+    /// `i` or `a` after an operator
+    TextObject(char),
+}
+
+/// Vi key bindings, starting every line in insert mode like readline does
+#[derive(Debug)]
+pub struct ViKeymap {
+    mode: Mode,
+    /// Insert mode keeps the usual readline bindings
+    insert: EmacsKeymap,
+    count: Option<usize>,
+    /// `d`, `c` or `y` waiting for a motion, with the count typed before it
+    operator: Option<(char, usize)>,
+    pending: Option<Pending>,
+    last_find: Option<(char, char)>,
+    /// Keys of the change being typed, from the normal mode key starting it
+    change: Vec<Key>,
+    /// Keys of the last complete change, replayed by `.`
+    last_change: Vec<Key>,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Vi splits words into runs of word characters, runs of punctuation and whitespace;
+/// WORDs are only separated by whitespace
+fn class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || is_word_char(c) {
+        1
+    } else {
+        2
+    }
+}
+
+fn next_word_start(chars: &[char], position: usize, big: bool) -> usize {
+    let mut i = position;
+    if i < chars.len() {
+        let start = class(chars[i], big);
+        while i < chars.len() && start != 0 && class(chars[i], big) == start {
+            i += 1;
+        }
+    }
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+fn word_end(chars: &[char], position: usize, big: bool) -> usize {
+    let mut i = position + 1;
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    if i >= chars.len() {
+        return chars.len().saturating_sub(1);
+    }
+    let end = class(chars[i], big);
+    while i + 1 < chars.len() && class(chars[i + 1], big) == end {
+        i += 1;
+    }
+    i
+}
+
+fn previous_word_start(chars: &[char], position: usize, big: bool) -> usize {
+    let mut i = position;
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    if i == 0 {
+        return 0;
+    }
+    let start = class(chars[i - 1], big);
+    while i > 0 && class(chars[i - 1], big) == start {
+        i -= 1;
+    }
+    i
+}
+
+fn first_non_blank(chars: &[char]) -> usize {
+    chars
+        .iter()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(chars.len())
+}
+
+fn find(chars: &[char], position: usize, kind: char, target: char) -> Option<usize> {
+    match kind {
+        'f' | 't' => {
+            let found = (position + 1..chars.len()).find(|&i| chars[i] == target)?;
+            Some(if kind == 't' { found - 1 } else { found })
+        }
+        _ => {
+            let found = (0..position).rev().find(|&i| chars[i] == target)?;
+            Some(if kind == 'T' { found + 1 } else { found })
+        }
+    }
+}
+
+/// Range of the text object `inner`/`around` + `object` under the cursor
+fn text_object(
+    chars: &[char],
+    position: usize,
+    around: bool,
+    object: char,
+) -> Option<(usize, usize)> {
+    match object {
+        'w' | 'W' => {
+            let big = object == 'W';
+            let here = class(*chars.get(position)?, big);
+            let mut start = position;
+            while start > 0 && class(chars[start - 1], big) == here {
+                start -= 1;
+            }
+            let mut end = position;
+            while end < chars.len() && class(chars[end], big) == here {
+                end += 1;
+            }
+            if around {
+                if end < chars.len() && chars[end].is_whitespace() {
+                    while end < chars.len() && chars[end].is_whitespace() {
+                        end += 1;
+                    }
+                } else {
+                    while start > 0 && chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                }
+            }
+            Some((start, end))
+        }
+        '"' | '\'' | '`' => {
+            let quotes: Vec<usize> = (0..chars.len()).filter(|&i| chars[i] == object).collect();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(_, close)| close >= position)?;
+            Some(if around {
+                (open, close + 1)
+            } else {
+                (open + 1, close)
+            })
+        }
+        _ => {
+            let (open_char, close_char) = match object {
+                '(' | ')' | 'b' => ('(', ')'),
+                '[' | ']' => ('[', ']'),
+                '{' | '}' | 'B' => ('{', '}'),
+                '<' | '>' => ('<', '>'),
+                _ => return None,
+            };
+            let mut depth = 0;
+            let mut open = None;
+            for i in (0..=position.min(chars.len().checked_sub(1)?)).rev() {
+                if chars[i] == close_char && i != position {
+                    depth += 1;
+                } else if chars[i] == open_char {
+                    if depth == 0 {
+                        open = Some(i);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let open = open?;
+            let mut depth = 0;
+            let close = (open + 1..chars.len()).find(|&i| {
+                if chars[i] == open_char {
+                    depth += 1;
+                } else if chars[i] == close_char {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                }
+                false
+            })?;
+            Some(if around {
+                (open, close + 1)
+            } else {
+                (open + 1, close)
+            })
+        }
+    }
+}
+
+/// Byte offset of the grapheme at `index`
+fn byte_offset(text: &str, index: usize) -> usize {
+    text.grapheme_indices(true)
+        .nth(index)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len())
+}
+
+impl Default for ViKeymap {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Insert,
+            insert: EmacsKeymap::default(),
+            count: None,
+            operator: None,
+            pending: None,
+            last_find: None,
+            change: Vec::new(),
+            last_change: Vec::new(),
+        }
+    }
+}
+
+impl ViKeymap {
+    pub fn is_normal(&self) -> bool {
+        self.mode == Mode::Normal
+    }
+
+    pub fn indicator(&self) -> &'static str {
+        match self.mode {
+            Mode::Insert => "(ins) ",
+            Mode::Normal => "(cmd) ",
+        }
+    }
+
+    /// Starts a new line in insert mode
+    pub fn reset(&mut self) {
+        *self = Self {
+            last_find: self.last_find,
+            last_change: std::mem::take(&mut self.last_change),
+            ..Self::default()
+        };
+    }
+
+    fn cancel(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.pending = None;
+    }
+
+    /// Keys of the last change, for the editor to feed back in on `.`
+    pub fn last_change(&self) -> Vec<Key> {
+        self.last_change.clone()
+    }
+
+    pub fn commands(&mut self, key: &Key, buffer: &Buffer) -> Vec<Command> {
+        let idle = self.count.is_none() && self.operator.is_none() && self.pending.is_none();
+        if self.mode == Mode::Normal && idle && *key == Key::Char('.') {
+            return vec![Command::RepeatChange];
+        }
+
+        let inserting = self.mode == Mode::Insert;
+        let commands = self.key(key, buffer);
+
+        // Record the keys of each change, including what is typed in insert mode after it
+        if inserting && self.change.is_empty() {
+            return commands;
+        }
+        self.change.push(key.clone());
+        if self.count.is_some() || self.operator.is_some() || self.pending.is_some() {
+            return commands;
+        }
+        let edits = commands.iter().any(|command| {
+            matches!(
+                command,
+                Command::Kill(..)
+                    | Command::Replace(..)
+                    | Command::ToggleCase(..)
+                    | Command::Paste { .. }
+            )
+        });
+        if self.mode == Mode::Normal {
+            if inserting || edits {
+                self.last_change = std::mem::take(&mut self.change);
+            } else {
+                self.change.clear();
+            }
+        }
+        commands
+    }
+
+    fn key(&mut self, key: &Key, buffer: &Buffer) -> Vec<Command> {
+        if self.mode == Mode::Insert {
+            if *key == Key::Escape {
+                self.mode = Mode::Normal;
+                return vec![Command::MoveLeft];
+            }
+            return self.insert.command(key).into_iter().collect();
+        }
+
+        // Motions count graphemes, each classified by its first character
+        let chars: Vec<char> = buffer
+            .text
+            .graphemes(true)
+            .filter_map(|g| g.chars().next())
+            .collect();
+        let cursor = buffer.text[..buffer.cursor].graphemes(true).count();
+        let byte = |index: usize| byte_offset(&buffer.text, index);
+
+        if let Some(pending) = self.pending.take() {
+            let Key::Char(c) = *key else {
+                self.cancel();
+                return Vec::new();
+            };
+            let count = self.take_count();
+            return match pending {
+                Pending::Find(kind) => {
+                    self.last_find = Some((kind, c));
+                    let target =
+                        (0..count).try_fold(cursor, |position, _| find(&chars, position, kind, c));
+                    match target {
+                        Some(target) => {
+                            self.motion(target, kind == 'f' || kind == 't', cursor, &byte)
+                        }
+                        None => {
+                            self.cancel();
+                            Vec::new()
+                        }
+                    }
+                }
+                Pending::Replace => {
+                    if cursor + count > chars.len() {
+                        return Vec::new();
+                    }
+                    vec![Command::Replace(byte(cursor), byte(cursor + count), c)]
+                }
+                Pending::TextObject(kind) => match text_object(&chars, cursor, kind == 'a', c) {
+                    Some((start, end)) => self.apply(start, end, cursor, &byte),
+                    None => {
+                        self.cancel();
+                        Vec::new()
+                    }
+                },
+            };
+        }
+
+        let c = match *key {
+            Key::Char(c) => c,
+            Key::ArrowLeft | Key::Backspace => 'h',
+            Key::ArrowRight => 'l',
+            Key::ArrowUp => 'k',
+            Key::ArrowDown => 'j',
+            Key::Home => '0',
+            Key::End => '$',
+            Key::Del => 'x',
+            Key::Enter => return vec![Command::Accept],
+            Key::Escape => {
+                self.cancel();
+                return Vec::new();
+            }
+            _ => return Vec::new(),
+        };
+
+        if c.is_ascii_digit() && (c != '0' || self.count.is_some()) {
+            let digit = c.to_digit(10).unwrap() as usize;
+            self.count = Some(
+                self.count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(digit),
+            );
+            return Vec::new();
+        }
+
+        if self.operator.is_some() && (c == 'i' || c == 'a') {
+            self.pending = Some(Pending::TextObject(c));
+            return Vec::new();
+        }
+
+        let motion = match c {
+            'h' => Some((cursor.saturating_sub(self.peek_count()), false)),
+            'l' | ' ' => Some(((cursor + self.peek_count()).min(chars.len()), false)),
+            '0' => Some((0, false)),
+            '^' => Some((first_non_blank(&chars), false)),
+            '$' => Some((chars.len().saturating_sub(1), true)),
+            'w' | 'W' => {
+                let big = c == 'W';
+                // cw changes to the end of the word, like ce
+                if self.operator.map(|(o, _)| o) == Some('c')
+                    && chars.get(cursor).is_some_and(|c| !c.is_whitespace())
+                {
+                    let mut target = cursor;
+                    for i in 0..self.peek_count() {
+                        let at_word_end = chars.get(target + 1).map(|c| class(*c, big))
+                            != Some(class(chars[target], big));
+                        if i > 0 || !at_word_end {
+                            target = word_end(&chars, target, big);
+                        }
+                    }
+                    Some((target, true))
+                } else {
+                    let target = (0..self.peek_count())
+                        .fold(cursor, |position, _| next_word_start(&chars, position, big));
+                    Some((target, false))
+                }
+            }
+            'b' | 'B' => {
+                let target = (0..self.peek_count()).fold(cursor, |position, _| {
+                    previous_word_start(&chars, position, c == 'B')
+                });
+                Some((target, false))
+            }
+            'e' | 'E' => {
+                let target = (0..self.peek_count())
+                    .fold(cursor, |position, _| word_end(&chars, position, c == 'E'));
+                Some((target, true))
+            }
+            ';' | ',' => {
+                let Some((kind, target)) = self.last_find else {
+                    return Vec::new();
+                };
+                let kind = if c == ',' {
+                    match kind {
+                        'f' => 'F',
+                        'F' => 'f',
+                        't' => 'T',
+                        _ => 't',
+                    }
+                } else {
+                    kind
+                };
+                let found = (0..self.peek_count())
+                    .try_fold(cursor, |position, _| find(&chars, position, kind, target));
+                match found {
+                    Some(found) => Some((found, kind == 'f' || kind == 't')),
+                    None => {
+                        self.cancel();
+                        return Vec::new();
+                    }
+                }
+            }
+            _ => None,
+        };
+        if let Some((target, inclusive)) = motion {
+            self.take_count();
+            return self.motion(target, inclusive, cursor, &byte);
+        }
+
+        let count = self.take_count();
+        match c {
+            'f' | 'F' | 't' | 'T' => {
+                self.count = Some(count);
+                self.pending = Some(Pending::Find(c));
+                Vec::new()
+            }
+            'r' => {
+                self.count = Some(count);
+                self.pending = Some(Pending::Replace);
+                Vec::new()
+            }
+            'd' | 'c' | 'y' => match self.operator {
+                // dd, cc and yy work on the whole line
+                Some((operator, _)) if operator == c => self.apply(0, chars.len(), cursor, &byte),
+                Some(_) => {
+                    self.cancel();
+                    Vec::new()
+                }
+                None => {
+                    self.operator = Some((c, count));
+                    Vec::new()
+                }
+            },
+            _ if self.operator.is_some() => {
+                self.cancel();
+                Vec::new()
+            }
+            'D' | 'C' => {
+                self.operator = Some((c.to_ascii_lowercase(), 1));
+                self.apply(cursor, chars.len(), cursor, &byte)
+            }
+            'Y' | 'S' => {
+                self.operator = Some((if c == 'Y' { 'y' } else { 'c' }, 1));
+                self.apply(0, chars.len(), cursor, &byte)
+            }
+            'x' | 's' if cursor < chars.len() => {
+                self.operator = Some((if c == 'x' { 'd' } else { 'c' }, 1));
+                self.apply(cursor, (cursor + count).min(chars.len()), cursor, &byte)
+            }
+            'X' if cursor > 0 => {
+                self.operator = Some(('d', 1));
+                self.apply(cursor.saturating_sub(count), cursor, cursor, &byte)
+            }
+            'i' => self.insert_at(byte(cursor)),
+            'a' => self.insert_at(byte((cursor + 1).min(chars.len()))),
+            'I' => self.insert_at(byte(first_non_blank(&chars))),
+            'A' => self.insert_at(buffer.text.len()),
+            'p' | 'P' => vec![Command::Paste { after: c == 'p' }; count],
+            '~' => {
+                let end = (cursor + count).min(chars.len());
+                if cursor == end {
+                    return Vec::new();
+                }
+                vec![Command::ToggleCase(byte(cursor), byte(end))]
+            }
+            'u' => vec![Command::Undo; count],
+            'k' | '-' => vec![Command::HistoryPrevious; count],
+            'j' | '+' => vec![Command::HistoryNext; count],
+            '/' | '\u{12}' => vec![Command::ReverseSearch],
+            'v' => vec![Command::EditExternally],
+            '\u{4}' => vec![Command::DeleteForwardOrEof],
+            '\u{c}' => vec![Command::ClearScreen],
+            _ => Vec::new(),
+        }
+    }
+
+    fn peek_count(&self) -> usize {
+        self.count.unwrap_or(1) * self.operator.map(|(_, count)| count).unwrap_or(1)
+    }
+
+    fn take_count(&mut self) -> usize {
+        let count = self.peek_count();
+        self.count = None;
+        if let Some((operator, _)) = self.operator {
+            self.operator = Some((operator, 1));
+        }
+        count
+    }
+
+    fn insert_at(&mut self, position: usize) -> Vec<Command> {
+        self.mode = Mode::Insert;
+        vec![Command::MoveTo(position)]
+    }
+
+    /// Moves to `target`, or applies the pending operator up to it
+    fn motion(
+        &mut self,
+        target: usize,
+        inclusive: bool,
+        cursor: usize,
+        byte: &dyn Fn(usize) -> usize,
+    ) -> Vec<Command> {
+        if self.operator.is_none() {
+            return vec![Command::MoveTo(byte(target))];
+        }
+        let (start, end) = if target < cursor {
+            (target, cursor)
+        } else {
+            (cursor, target)
+        };
+        let end = if inclusive { end + 1 } else { end };
+        self.apply(start, end, cursor, byte)
+    }
+
+    /// Applies the pending operator to the characters `start..end`
+    fn apply(
+        &mut self,
+        start: usize,
+        end: usize,
+        cursor: usize,
+        byte: &dyn Fn(usize) -> usize,
+    ) -> Vec<Command> {
+        let Some((operator, _)) = self.operator.take() else {
+            return Vec::new();
+        };
+        self.count = None;
+        let (start, end) = (byte(start), byte(end));
+        match operator {
+            'y' => vec![
+                Command::Copy(start, end),
+                Command::MoveTo(byte(cursor).min(start)),
+            ],
+            'c' => {
+                self.mode = Mode::Insert;
+                vec![Command::Kill(start, end)]
+            }
+            _ => vec![Command::Kill(start, end)],
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use console::{measure_text_width, pad_str, truncate_str, Alignment, Key, Style, Term};
+use std::io::{ErrorKind, Write};
+
+use crate::connector::MySqlOutputTable;
+use crate::formatter::truncate;
+use crate::signal::ignore_interrupts;
+
+/// Widest a column starts out, longer values are cut short until it is widened
+const MAX_INITIAL_WIDTH: usize = 40;
+
+/// Full screen grid for browsing a result, opened with `\view`
+pub struct Viewer<'a> {
+    table: &'a MySqlOutputTable,
+    term: Term,
+    widths: Vec<usize>,
+    hidden: Vec<bool>,
+    /// Selected cell
+    row: usize,
+    column: usize,
+    /// First row on screen
+    top: usize,
+    /// How many columns after the frozen one are scrolled out to the left
+    left: usize,
+    detail: bool,
+    search: Option<String>,
+    message: Option<String>,
+}
+
+/// Switches to the alternate screen with the cursor hidden until dropped, so the terminal is
+/// restored however the viewer ends
+struct AlternateScreen {
+    term: Term,
+}
+
+impl AlternateScreen {
+    fn enter(term: &Term) -> std::io::Result<Self> {
+        term.write_str("\x1b[?1049h")?;
+        let screen = Self { term: term.clone() };
+        term.hide_cursor()?;
+        Ok(screen)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        // Nothing useful to do if the terminal is gone
+        let _ = self.term.show_cursor();
+        let _ = self.term.write_str("\x1b[?1049l");
+    }
+}
+
+/// Makes a value fit on one line of the grid
+fn flatten(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '\n' => '↵',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+/// Breaks `text` into lines no wider than `width`
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in text.replace('\t', "    ").lines() {
+        let mut current = String::new();
+        for c in line.chars() {
+            if measure_text_width(&current) + measure_text_width(c.encode_utf8(&mut [0; 4])) > width
+            {
+                lines.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+impl<'a> Viewer<'a> {
+    pub fn new(table: &'a MySqlOutputTable) -> Self {
+        let widths = table
+            .headers
+            .iter()
+            .map(|header| {
+                table
+                    .values
+                    .iter()
+                    .map(|row| measure_text_width(&flatten(&row[header])))
+                    .chain(std::iter::once(measure_text_width(header)))
+                    .max()
+                    .unwrap_or(0)
+                    .clamp(1, MAX_INITIAL_WIDTH)
+            })
+            .collect();
+        Self {
+            table,
+            term: Term::stdout(),
+            widths,
+            hidden: vec![false; table.headers.len()],
+            row: 0,
+            column: 0,
+            top: 0,
+            left: 0,
+            detail: false,
+            search: None,
+            message: None,
+        }
+    }
+
+    /// Shows the grid on the alternate screen until the user quits
+    pub fn run(&mut self) -> std::io::Result<()> {
+        let _interrupts = ignore_interrupts();
+        let _screen = AlternateScreen::enter(&self.term)?;
+        self.event_loop()
+    }
+
+    fn event_loop(&mut self) -> std::io::Result<()> {
+        let rows = self.table.values.len();
+        loop {
+            let page = self.draw()?.max(1);
+            let key = match self.term.read_key() {
+                // Ctrl-C
+                Err(e) if e.kind() == ErrorKind::Interrupted => return Ok(()),
+                key => key?,
+            };
+            self.message = None;
+            match key {
+                Key::Char('q') | Key::Escape => return Ok(()),
+                Key::ArrowUp | Key::Char('k') => self.row = self.row.saturating_sub(1),
+                Key::ArrowDown | Key::Char('j') => self.row = (self.row + 1).min(rows - 1),
+                Key::PageUp | Key::Char('b') => self.row = self.row.saturating_sub(page),
+                Key::PageDown | Key::Char(' ') => self.row = (self.row + page).min(rows - 1),
+                Key::Home | Key::Char('g') => self.row = 0,
+                Key::End | Key::Char('G') => self.row = rows - 1,
+                Key::ArrowLeft | Key::Char('h') => self.step_column(false),
+                Key::ArrowRight | Key::Char('l') | Key::Tab => self.step_column(true),
+                Key::Char('0' | '^') => self.column = self.visible_columns()[0],
+                Key::Char('$') => self.column = *self.visible_columns().last().unwrap(),
+                Key::Char('<') => self.widths[self.column] = (self.widths[self.column] - 1).max(1),
+                Key::Char('>') => self.widths[self.column] += 1,
+                Key::Char('-') => self.hide_column(),
+                Key::Char('+') => self.hidden.fill(false),
+                Key::Enter => self.detail = !self.detail,
+                Key::Char('/') => {
+                    if let Some(pattern) = self.prompt("/")? {
+                        if !pattern.is_empty() {
+                            self.search = Some(pattern.to_lowercase());
+                        }
+                        self.find(true);
+                    }
+                }
+                Key::Char('n') => self.find(true),
+                Key::Char('N') => self.find(false),
+                Key::Char(':') => {
+                    if let Some(row) = self.prompt("Row: ")? {
+                        match row.trim().parse::<usize>() {
+                            Ok(row) => self.row = row.clamp(1, rows) - 1,
+                            Err(_) => self.message = Some(format!("Not a row number: {}", row)),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn visible_columns(&self) -> Vec<usize> {
+        (0..self.table.headers.len())
+            .filter(|&column| !self.hidden[column])
+            .collect()
+    }
+
+    fn step_column(&mut self, forward: bool) {
+        let columns = self.visible_columns();
+        let position = columns.iter().position(|&c| c == self.column).unwrap_or(0);
+        let position = if forward {
+            (position + 1).min(columns.len() - 1)
+        } else {
+            position.saturating_sub(1)
+        };
+        self.column = columns[position];
+    }
+
+    /// Hides the selected column, keeping at least one on screen
+    fn hide_column(&mut self) {
+        let columns = self.visible_columns();
+        if columns.len() == 1 {
+            self.message = Some("Cannot hide the last column".to_string());
+            return;
+        }
+        let position = columns.iter().position(|&c| c == self.column).unwrap_or(0);
+        self.hidden[self.column] = true;
+        self.column = columns
+            .get(position + 1)
+            .copied()
+            .unwrap_or(columns[position - 1]);
+    }
+
+    fn cell(&self, row: usize, column: usize) -> &str {
+        &self.table.values[row][&self.table.headers[column]]
+    }
+
+    /// Moves to the next cell containing the search, row by row and wrapping around the end
+    fn find(&mut self, forward: bool) {
+        let Some(search) = &self.search else {
+            self.message = Some("No previous search".to_string());
+            return;
+        };
+        let columns = self.visible_columns();
+        let cells: Vec<(usize, usize)> = (0..self.table.values.len())
+            .flat_map(|row| columns.iter().map(move |&column| (row, column)))
+            .collect();
+        let current = cells
+            .iter()
+            .position(|&cell| cell == (self.row, self.column))
+            .unwrap_or(0);
+        let count = cells.len();
+        let found = (1..=count)
+            .map(|step| {
+                if forward {
+                    (current + step) % count
+                } else {
+                    (current + count - step) % count
+                }
+            })
+            .find(|&i| {
+                let (row, column) = cells[i];
+                self.cell(row, column).to_lowercase().contains(search)
+            });
+        match found {
+            Some(i) => (self.row, self.column) = cells[i],
+            None => self.message = Some(format!("Pattern not found: {}", search)),
+        }
+    }
+
+    /// Reads a line of input on the status line, or `None` when cancelled with Escape
+    fn prompt(&mut self, label: &str) -> std::io::Result<Option<String>> {
+        let mut input = String::new();
+        self.term.show_cursor()?;
+        let result = loop {
+            let (rows, _) = self.term.size();
+            self.term
+                .write_str(&format!("\x1b[{};1H\x1b[K{}{}", rows, label, input))?;
+            let key = match self.term.read_key() {
+                Err(e) if e.kind() == ErrorKind::Interrupted => Key::Escape,
+                key => key?,
+            };
+            match key {
+                Key::Enter => break Some(input),
+                Key::Escape => break None,
+                Key::Backspace => {
+                    input.pop();
+                }
+                Key::Char(c) if !c.is_control() => input.push(c),
+                _ => {}
+            }
+        };
+        self.term.hide_cursor()?;
+        Ok(result)
+    }
+
+    /// Scrolls so the selected column fits next to the frozen one within `width`
+    fn scroll_columns(&mut self, columns: &[usize], width: usize) {
+        // Hiding columns can leave fewer than were scrolled past
+        self.left = self.left.min(columns.len() - 1);
+        let Some(position) = columns.iter().position(|&c| c == self.column) else {
+            return;
+        };
+        if position == 0 {
+            return;
+        }
+        self.left = self.left.min(position - 1);
+        loop {
+            let used: usize = std::iter::once(columns[0])
+                .chain(columns[1 + self.left..=position].iter().copied())
+                .map(|c| self.widths[c] + 3)
+                .sum();
+            if used <= width + 3 || self.left + 1 >= position {
+                break;
+            }
+            self.left += 1;
+        }
+    }
+
+    fn render_row(
+        &self,
+        columns: &[usize],
+        cell: impl Fn(usize) -> String,
+        row: Option<usize>,
+    ) -> String {
+        let frozen = columns[0];
+        std::iter::once(frozen)
+            .chain(columns[1 + self.left..].iter().copied())
+            .enumerate()
+            .map(|(i, column)| {
+                let width = self.widths[column];
+                let text = cell(column);
+                let text =
+                    pad_str(&truncate(&text, width), width, Alignment::Left, None).to_string();
+                let text = if row == Some(self.row) && column == self.column {
+                    Style::new().reverse().apply_to(text).to_string()
+                } else if row.is_none() {
+                    Style::new().bold().apply_to(text).to_string()
+                } else {
+                    text
+                };
+                let separator = if i == 0 { " ┃ " } else { " │ " };
+                format!("{}{}", text, separator)
+            })
+            .collect::<String>()
+    }
+
+    /// Lines of the pane showing the whole selected value, pretty-printing JSON
+    fn detail_lines(&self, width: usize) -> Vec<String> {
+        let header = &self.table.headers[self.column];
+        let value = self.cell(self.row, self.column);
+        let value = match serde_json::from_str::<serde_json::Value>(value) {
+            Ok(json @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) => {
+                serde_json::to_string_pretty(&json).unwrap_or_else(|_| value.to_string())
+            }
+            _ => value.to_string(),
+        };
+        let title = format!("── {} ", header);
+        let mut lines = vec![format!(
+            "{}{}",
+            title,
+            "─".repeat(width.saturating_sub(measure_text_width(&title)))
+        )];
+        lines.extend(wrap(&value, width.max(1)));
+        lines
+    }
+
+    /// Redraws the screen and returns how many rows of the grid it shows
+    fn draw(&mut self) -> std::io::Result<usize> {
+        let (rows, width) = self.term.size();
+        let (rows, width) = (rows as usize, width as usize);
+        let columns = self.visible_columns();
+
+        let mut pane = if self.detail {
+            self.detail_lines(width)
+        } else {
+            Vec::new()
+        };
+        pane.truncate(rows / 2);
+        // Header, its rule and the status line
+        let body = rows.saturating_sub(3 + pane.len());
+
+        if self.row < self.top {
+            self.top = self.row;
+        } else if body > 0 && self.row >= self.top + body {
+            self.top = self.row + 1 - body;
+        }
+        self.scroll_columns(&columns, width);
+
+        let mut lines = Vec::with_capacity(rows);
+        lines.push(self.render_row(&columns, |c| flatten(&self.table.headers[c]), None));
+        lines.push("─".repeat(width));
+        for row in self.top..self.top + body {
+            if row < self.table.values.len() {
+                lines.push(self.render_row(&columns, |c| flatten(self.cell(row, c)), Some(row)));
+            } else {
+                lines.push(Style::new().dim().apply_to("~").to_string());
+            }
+        }
+        lines.extend(pane);
+
+        let status = match &self.message {
+            Some(message) => message.clone(),
+            None => format!(
+                "row {}/{}  column {}/{}  {}",
+                self.row + 1,
+                self.table.values.len(),
+                self.column + 1,
+                self.table.headers.len(),
+                "q quit, / search, : row, Enter detail, < > width, - hide, + show all"
+            ),
+        };
+        lines.push(
+            Style::new()
+                .reverse()
+                .apply_to(pad_str(
+                    &truncate(&status, width),
+                    width,
+                    Alignment::Left,
+                    None,
+                ))
+                .to_string(),
+        );
+
+        let screen = lines
+            .iter()
+            .map(|line| truncate_str(line, width, "").to_string())
+            .collect::<Vec<_>>()
+            .join("\x1b[K\r\n");
+        let mut stdout = std::io::stdout();
+        write!(stdout, "\x1b[H{}\x1b[K\x1b[J", screen)?;
+        stdout.flush()?;
+        Ok(body)
+    }
+}